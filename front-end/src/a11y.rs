@@ -0,0 +1,114 @@
+//! Describes what happens on the canvas for screen readers, through an ARIA live region
+use js_sys::Error;
+use wasm_bindgen::JsValue;
+use web_sys::Document;
+
+use crate::game::Effect;
+use crate::highscores::Submission;
+use crate::snake::Direction;
+
+/// What to announce for an effect, if anything
+pub fn describe(effect: &Effect, announce_turns: bool) -> Option<String> {
+    match effect {
+        Effect::NewGame => Some(String::from("Game started")),
+        Effect::AteApple(apples) => Some(format!("Apple eaten. {}", apple_count(*apples))),
+        Effect::Turned(dir) if announce_turns => Some(String::from(direction_name(*dir))),
+        Effect::GameOver { apples, ending } => Some(format!(
+            "Game over: {}. Final score: {}",
+            ending,
+            apple_count(*apples)
+        )),
+        Effect::Help => Some(String::from("Help shown. Key bindings follow.")),
+        Effect::NewBest(_) => Some(String::from("New personal best")),
+        Effect::NameEntry(true) => Some(String::from(
+            "New highscore! Type your name and press Enter to submit it, or Escape to skip. \
+             Your name and score will be shown publicly.",
+        )),
+        Effect::Muted(true) => Some(String::from("Sound off")),
+        Effect::Muted(false) => Some(String::from("Sound on")),
+        Effect::Status(_)
+        | Effect::AppleCount(_)
+        | Effect::Turned(_)
+        | Effect::ControlScheme(_)
+        | Effect::NameEntry(false) => None,
+    }
+}
+
+pub fn describe_submission(submission: &Submission) -> &'static str {
+    match submission {
+        Submission::NotAHighscore => "Not a highscore this time",
+        Submission::Submitted(_) => "New highscore submitted",
+        Submission::Queued(_) => {
+            "New highscore saved. It will be submitted once the highscore server can be reached"
+        }
+        Submission::Declined => "New highscore, but it was not submitted",
+    }
+}
+
+fn apple_count(apples: u8) -> String {
+    format!(
+        "{} {}",
+        apples,
+        if apples == 1 { "apple" } else { "apples" }
+    )
+}
+
+fn direction_name(dir: Direction) -> &'static str {
+    match dir {
+        Direction::Up => "up",
+        Direction::Down => "down",
+        Direction::Left => "left",
+        Direction::Right => "right",
+    }
+}
+
+/// Reads the message out loud, through the live region
+pub fn announce(doc: &Document, message: &str) -> Result<(), JsValue> {
+    let region = doc
+        .get_element_by_id("announcer")
+        .ok_or_else(|| Error::new("Document had no live region"))?;
+    region.set_text_content(Some(message));
+    Ok(())
+}
+
+/// Puts the help text in the document, where screen readers can find it
+pub fn set_help_text(doc: &Document, help: &str) -> Result<(), JsValue> {
+    let element = doc
+        .get_element_by_id("help-text")
+        .ok_or_else(|| Error::new("Document had no help text element"))?;
+    element.set_text_content(Some(help));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::Ending;
+    use crate::snake::Collision;
+
+    #[test]
+    fn game_over_should_give_reason_and_score() {
+        let effect = Effect::GameOver {
+            apples: 1,
+            ending: Ending::Crashed(Collision::Itself),
+        };
+        assert_eq!(
+            Some(String::from(
+                "Game over: crashed into yourself. Final score: 1 apple"
+            )),
+            describe(&effect, false)
+        );
+    }
+
+    #[test]
+    fn turns_should_only_be_announced_when_asked_for() {
+        let effect = Effect::Turned(Direction::Left);
+        assert_eq!(None, describe(&effect, false));
+        assert_eq!(Some(String::from("left")), describe(&effect, true));
+    }
+
+    #[test]
+    fn apple_counter_updates_should_not_be_announced() {
+        assert_eq!(None, describe(&Effect::AppleCount(3), true));
+    }
+}
@@ -0,0 +1,124 @@
+//! Drives the game with `requestAnimationFrame`, stepping the game logic on a fixed timestep
+use std::cell::RefCell;
+use std::pin::Pin;
+use std::rc::Rc;
+use std::task::{Context, Poll};
+
+use futures::channel::mpsc;
+use futures::stream::Stream;
+use gloo_utils::window;
+use wasm_bindgen::closure::Closure;
+use wasm_bindgen::JsCast;
+
+/// Most ticks to catch up on in one frame, so a throttled tab doesn't fast-forward the game
+const MAX_TICKS_PER_FRAME: u32 = 3;
+
+/// Turns frame timestamps into a number of fixed-length ticks to run
+#[derive(Debug, Default)]
+pub struct Timestep {
+    last_frame_at: Option<f64>,
+    /// Time passed, in milliseconds, which has not been used up by ticks yet
+    accumulated: f64,
+}
+
+impl Timestep {
+    /// Returns how many ticks to run for a frame at the given timestamp,
+    /// and how far, from 0 to 1, the game has come towards the next tick
+    pub fn advance(&mut self, now: f64, tick_millis: f64) -> (u32, f64) {
+        let elapsed = self.last_frame_at.map_or(0.0, |last| (now - last).max(0.0));
+        self.last_frame_at = Some(now);
+        self.accumulated += elapsed;
+
+        let mut ticks = 0;
+        while self.accumulated >= tick_millis {
+            self.accumulated -= tick_millis;
+            ticks += 1;
+        }
+        if ticks > MAX_TICKS_PER_FRAME {
+            ticks = MAX_TICKS_PER_FRAME;
+            self.accumulated = 0.0;
+        }
+
+        (ticks, self.accumulated / tick_millis)
+    }
+}
+
+type FrameCallback = Rc<RefCell<Option<Closure<dyn FnMut(f64)>>>>;
+
+/// Timestamps of animation frames, from `requestAnimationFrame`
+pub struct AnimationFrames {
+    receiver: mpsc::UnboundedReceiver<f64>,
+    // Keeps the callback alive. It's dropped, and the loop stops, along with the stream.
+    _callback: FrameCallback,
+}
+
+impl AnimationFrames {
+    pub fn new() -> Self {
+        let (sender, receiver) = mpsc::unbounded();
+        let callback: FrameCallback = Rc::new(RefCell::new(None));
+
+        *callback.borrow_mut() = Some(Closure::new({
+            let callback = Rc::downgrade(&callback);
+            move |timestamp| {
+                if sender.unbounded_send(timestamp).is_err() {
+                    return;
+                }
+                if let Some(callback) = callback.upgrade() {
+                    request_frame(callback.borrow().as_ref().unwrap());
+                }
+            }
+        }));
+        request_frame(callback.borrow().as_ref().unwrap());
+
+        Self {
+            receiver,
+            _callback: callback,
+        }
+    }
+}
+
+fn request_frame(callback: &Closure<dyn FnMut(f64)>) {
+    window()
+        .request_animation_frame(callback.as_ref().unchecked_ref())
+        .expect("should be able to request animation frames");
+}
+
+impl Stream for AnimationFrames {
+    type Item = f64;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        Pin::new(&mut self.receiver).poll_next(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn first_frame_should_not_tick() {
+        assert_eq!((0, 0.0), Timestep::default().advance(1000.0, 300.0));
+    }
+
+    #[test]
+    fn frames_should_tick_once_per_tick_length() {
+        let mut timestep = Timestep::default();
+        timestep.advance(0.0, 300.0);
+
+        assert_eq!((0, 0.5), timestep.advance(150.0, 300.0));
+        assert_eq!((1, 0.0), timestep.advance(300.0, 300.0));
+        assert_eq!((2, 0.25), timestep.advance(975.0, 300.0));
+    }
+
+    #[test]
+    fn long_gap_should_not_fast_forward() {
+        let mut timestep = Timestep::default();
+        timestep.advance(0.0, 300.0);
+
+        assert_eq!(
+            (MAX_TICKS_PER_FRAME, 0.0),
+            timestep.advance(60_000.0, 300.0)
+        );
+        assert_eq!((0, 0.5), timestep.advance(60_150.0, 300.0));
+    }
+}
@@ -0,0 +1,247 @@
+//! Parses and completes the ex-style commands which can be typed after pressing `:`
+use std::fmt;
+
+use crate::settings::MAX_SPEED;
+
+const COMMANDS: [&str; 5] = ["help", "quit", "restart", "seed", "set"];
+const OPTIONS: [&str; 5] = ["norelative", "nowrap", "relative", "speed=", "wrap"];
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ExCommand {
+    Quit,
+    Restart,
+    Help,
+    Set(Setting),
+    /// Use this seed for placing apples from the next game on
+    Seed(u64),
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Setting {
+    Speed(u8),
+    Wrap(bool),
+    /// Turn left and right relative to the snake's heading, instead of steering in absolute directions
+    Relative(bool),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ParseError {
+    NotACommand(String),
+    UnknownOption(String),
+    InvalidArgument(String),
+    TrailingCharacters(String),
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Mimic the error codes and messages vim gives
+        match self {
+            Self::NotACommand(s) => write!(f, "E492: Not an editor command: {s}"),
+            Self::UnknownOption(s) => write!(f, "E518: Unknown option: {s}"),
+            Self::InvalidArgument(s) => write!(f, "E474: Invalid argument: {s}"),
+            Self::TrailingCharacters(s) => write!(f, "E488: Trailing characters: {s}"),
+        }
+    }
+}
+
+/// Parses a command line, excluding the leading `:`
+pub fn parse(line: &str) -> Result<ExCommand, ParseError> {
+    let line = line.trim();
+    let (name, arg) = line
+        .split_once(char::is_whitespace)
+        .map_or((line, ""), |(name, arg)| (name, arg.trim()));
+
+    let no_arg = |cmd| {
+        if arg.is_empty() {
+            Ok(cmd)
+        } else {
+            Err(ParseError::TrailingCharacters(arg.to_string()))
+        }
+    };
+
+    match name {
+        "q" | "quit" => no_arg(ExCommand::Quit),
+        "restart" => no_arg(ExCommand::Restart),
+        "h" | "help" => no_arg(ExCommand::Help),
+        "se" | "set" => parse_setting(arg).map(ExCommand::Set),
+        "seed" => arg
+            .parse()
+            .map(ExCommand::Seed)
+            .map_err(|_| ParseError::InvalidArgument(line.to_string())),
+        _ => Err(ParseError::NotACommand(line.to_string())),
+    }
+}
+
+fn parse_setting(arg: &str) -> Result<Setting, ParseError> {
+    match arg.split_once('=') {
+        Some(("speed", speed)) => speed
+            .parse()
+            .ok()
+            .filter(|speed| (1..=MAX_SPEED).contains(speed))
+            .map(Setting::Speed)
+            .ok_or_else(|| ParseError::InvalidArgument(arg.to_string())),
+        None if arg == "wrap" => Ok(Setting::Wrap(true)),
+        None if arg == "nowrap" => Ok(Setting::Wrap(false)),
+        None if arg == "relative" => Ok(Setting::Relative(true)),
+        None if arg == "norelative" => Ok(Setting::Relative(false)),
+        _ => Err(ParseError::UnknownOption(arg.to_string())),
+    }
+}
+
+/// Completes the command or option being typed as far as it is unambiguous
+pub fn complete(line: &str) -> String {
+    match line.split_once(' ') {
+        None => match candidates(&COMMANDS, line).as_slice() {
+            [cmd @ ("set" | "seed")] => format!("{cmd} "),
+            [cmd] => cmd.to_string(),
+            cmds => common_prefix(cmds).unwrap_or(line).to_string(),
+        },
+        Some(("set", option)) => {
+            let completed = common_prefix(&candidates(&OPTIONS, option)).unwrap_or(option);
+            format!("set {completed}")
+        }
+        Some(_) => line.to_string(),
+    }
+}
+
+fn candidates<'a>(words: &[&'a str], prefix: &str) -> Vec<&'a str> {
+    words
+        .iter()
+        .filter(|w| w.starts_with(prefix))
+        .copied()
+        .collect()
+}
+
+fn common_prefix<'a>(words: &[&'a str]) -> Option<&'a str> {
+    let (first, rest) = words.split_first()?;
+    let len = rest.iter().fold(first.len(), |len, w| {
+        first
+            .bytes()
+            .zip(w.bytes())
+            .take(len)
+            .take_while(|(a, b)| a == b)
+            .count()
+    });
+    Some(&first[..len])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_parses_quit() {
+        assert_eq!(Ok(ExCommand::Quit), parse("q"));
+        assert_eq!(Ok(ExCommand::Quit), parse("quit"));
+    }
+
+    #[test]
+    fn it_parses_restart_and_help() {
+        assert_eq!(Ok(ExCommand::Restart), parse("restart"));
+        assert_eq!(Ok(ExCommand::Help), parse("help"));
+    }
+
+    #[test]
+    fn it_parses_set_speed() {
+        assert_eq!(Ok(ExCommand::Set(Setting::Speed(5))), parse("set speed=5"));
+    }
+
+    #[test]
+    fn it_refuses_speed_out_of_range() {
+        assert!(matches!(
+            parse("set speed=0"),
+            Err(ParseError::InvalidArgument(_))
+        ));
+        assert!(matches!(
+            parse("set speed=11"),
+            Err(ParseError::InvalidArgument(_))
+        ));
+    }
+
+    #[test]
+    fn it_parses_set_wrap_and_nowrap() {
+        assert_eq!(Ok(ExCommand::Set(Setting::Wrap(true))), parse("set wrap"));
+        assert_eq!(Ok(ExCommand::Set(Setting::Wrap(false))), parse("se nowrap"));
+    }
+
+    #[test]
+    fn it_parses_set_relative_and_norelative() {
+        assert_eq!(
+            Ok(ExCommand::Set(Setting::Relative(true))),
+            parse("set relative")
+        );
+        assert_eq!(
+            Ok(ExCommand::Set(Setting::Relative(false))),
+            parse("set norelative")
+        );
+    }
+
+    #[test]
+    fn it_parses_seed() {
+        assert_eq!(Ok(ExCommand::Seed(1234)), parse("seed 1234"));
+        assert!(matches!(
+            parse("seed abc"),
+            Err(ParseError::InvalidArgument(_))
+        ));
+    }
+
+    #[test]
+    fn it_ignores_surrounding_whitespace() {
+        assert_eq!(Ok(ExCommand::Quit), parse("  q "));
+    }
+
+    #[test]
+    fn it_refuses_unknown_commands_and_options() {
+        assert_eq!(
+            Err(ParseError::NotACommand(String::from("wq"))),
+            parse("wq")
+        );
+        assert_eq!(
+            Err(ParseError::UnknownOption(String::from("number"))),
+            parse("set number")
+        );
+    }
+
+    #[test]
+    fn it_refuses_trailing_characters() {
+        assert_eq!(
+            Err(ParseError::TrailingCharacters(String::from("now"))),
+            parse("q now")
+        );
+    }
+
+    #[test]
+    fn it_formats_errors_like_vim() {
+        let err = ParseError::NotACommand(String::from("wq"));
+        assert_eq!("E492: Not an editor command: wq", err.to_string());
+    }
+
+    #[test]
+    fn it_completes_unambiguous_commands() {
+        assert_eq!("restart", complete("r"));
+        assert_eq!("help", complete("he"));
+    }
+
+    #[test]
+    fn it_completes_commands_taking_arguments_with_a_space() {
+        assert_eq!("set ", complete("set"));
+    }
+
+    #[test]
+    fn it_completes_ambiguous_commands_as_far_as_possible() {
+        assert_eq!("se", complete("s"));
+    }
+
+    #[test]
+    fn it_completes_options() {
+        assert_eq!("set speed=", complete("set sp"));
+        assert_eq!("set no", complete("set n"));
+        assert_eq!("set nowrap", complete("set now"));
+    }
+
+    #[test]
+    fn it_leaves_unknown_input_alone() {
+        assert_eq!("xyz", complete("xyz"));
+        assert_eq!("seed 12", complete("seed 12"));
+    }
+}
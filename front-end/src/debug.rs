@@ -0,0 +1,124 @@
+//! Developer overlay showing the engine's state, for reproducing bugs.
+//! Toggled with a key which isn't in the keymap, or turned on with `?debug=1` in the url.
+use gloo_utils::window;
+use serde::Deserialize;
+
+use crate::game::Game;
+use crate::keymap::display_key;
+use crate::snake::{Direction, Position};
+
+/// Toggles the overlay. It's left out of the help on purpose.
+pub const DEBUG_KEY: &str = "F3";
+
+/// Weight of the latest tick when averaging jitter
+const JITTER_SMOOTHING: f64 = 0.1;
+
+#[derive(Debug, Default, Deserialize)]
+struct Query {
+    debug: Option<u8>,
+}
+
+/// Whether the page was opened with `?debug=1`
+pub fn enabled_by_url() -> bool {
+    let search = window().location().search().unwrap_or_default();
+    serde_qs::from_str::<Query>(search.trim_start_matches('?'))
+        .is_ok_and(|query| query.debug.is_some_and(|debug| debug > 0))
+}
+
+/// How long ticks actually take, compared to how long they should
+#[derive(Debug, Default)]
+pub struct TickTimer {
+    last_tick_at: Option<f64>,
+    /// Time between the last two ticks, in milliseconds
+    pub duration: f64,
+    /// Average difference between a tick's duration and the intended one, in milliseconds
+    pub jitter: f64,
+}
+
+impl TickTimer {
+    pub fn record(&mut self, now: f64, tick_millis: f64) {
+        if let Some(last) = self.last_tick_at {
+            self.duration = now - last;
+            let off_by = (self.duration - tick_millis).abs();
+            self.jitter += (off_by - self.jitter) * JITTER_SMOOTHING;
+        }
+        self.last_tick_at = Some(now);
+    }
+
+    /// Forgets the last tick, so time spent paused doesn't count as one long tick
+    pub fn reset(&mut self) {
+        self.last_tick_at = None;
+    }
+}
+
+fn cell(pos: &Position) -> String {
+    format!("{},{}", pos.column(), pos.row())
+}
+
+fn arrow(dir: Direction) -> &'static str {
+    match dir {
+        Direction::Up => display_key("ArrowUp"),
+        Direction::Down => display_key("ArrowDown"),
+        Direction::Left => display_key("ArrowLeft"),
+        Direction::Right => display_key("ArrowRight"),
+    }
+}
+
+/// Lines of text describing the game's state
+pub fn overlay(game: &Game, timer: &TickTimer) -> Vec<String> {
+    let snake = &game.snake;
+    vec![
+        format!("head {} tail {}", cell(snake.head()), cell(snake.tail())),
+        format!(
+            "apple {}",
+            snake
+                .target
+                .as_ref()
+                .map_or_else(|| String::from("-"), cell)
+        ),
+        format!("length {}", snake.body.len()),
+        format!(
+            "dir {} next {}",
+            arrow(snake.direction),
+            game.pending_direction().map_or("-", arrow)
+        ),
+        format!("tick {:.0}ms ±{:.1}ms", timer.duration, timer.jitter),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::Settings;
+    use std::sync::{Arc, RwLock};
+
+    #[test]
+    fn timer_should_measure_time_between_ticks() {
+        let mut timer = TickTimer::default();
+        timer.record(1000.0, 300.0);
+        timer.record(1310.0, 300.0);
+
+        assert_eq!(310.0, timer.duration);
+        assert_eq!(1.0, timer.jitter);
+    }
+
+    #[test]
+    fn overlay_should_describe_the_snake() {
+        let game = Game::new(Arc::new(RwLock::new(Settings::default())));
+        let lines = overlay(&game, &TickTimer::default());
+
+        assert_eq!("head 0,0 tail 0,0", lines[0]);
+        assert_eq!("length 1", lines[2]);
+        assert_eq!("dir → next -", lines[3]);
+    }
+
+    #[test]
+    fn timer_should_not_count_time_after_reset() {
+        let mut timer = TickTimer::default();
+        timer.record(1000.0, 300.0);
+        timer.reset();
+        timer.record(5000.0, 300.0);
+
+        assert_eq!(0.0, timer.duration);
+    }
+}
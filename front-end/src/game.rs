@@ -0,0 +1,1116 @@
+//! The game controller: a state machine turning input into state changes and things to render.
+//!
+//! It knows nothing about the DOM, so that it can be tested natively.
+use std::collections::VecDeque;
+use std::fmt;
+use std::sync::{Arc, RwLock};
+
+use futures::channel::oneshot;
+
+use crate::cmdline::{ExCommand, Setting};
+use crate::ghost::{Ghost, Replay};
+use crate::keymap::{Action, Keymap};
+use crate::name_entry::NameEntry;
+use crate::render::{Frame, Layer, Text, Tween};
+use crate::settings::Settings;
+use crate::snake::{Collision, Direction, Position, Snake};
+use crate::vi::{Command, ControlScheme};
+use crate::GameStatus;
+
+#[derive(Debug)]
+pub enum Input {
+    Command(Command),
+    /// Time for the snake to take another step
+    Tick,
+    /// Where the last game's score would place on the highscore table
+    Rank(u8),
+    /// Asks the player for a name for the highscore table, starting out with `prefill`
+    AskName {
+        prefill: String,
+        reply: oneshot::Sender<Option<String>>,
+    },
+}
+
+/// Something outside of the canvas which should happen as the result of an input
+#[derive(Debug, PartialEq)]
+pub enum Effect {
+    Status(GameStatus),
+    AppleCount(u8),
+    NewGame,
+    AteApple(u8),
+    Turned(Direction),
+    Help,
+    Muted(bool),
+    /// The control scheme was changed from the command line
+    ControlScheme(ControlScheme),
+    GameOver {
+        apples: u8,
+        ending: Ending,
+    },
+    /// The game which just ended is the best one yet
+    NewBest(Replay),
+    /// Keys should be typed in as text while this is on
+    NameEntry(bool),
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Ending {
+    Crashed(Collision),
+    Quit,
+}
+
+impl fmt::Display for Ending {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let reason = match self {
+            Ending::Crashed(Collision::Wall) => "crashed into the wall",
+            Ending::Crashed(Collision::Itself) => "crashed into yourself",
+            Ending::Quit => "quit",
+        };
+        write!(f, "{reason}")
+    }
+}
+
+/// How the last game went
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Summary {
+    pub apples: u8,
+    pub ending: Ending,
+    /// Time spent playing, not counting pauses
+    pub millis: u32,
+    pub tiles: u32,
+    pub top_speed: u8,
+    /// Place on the highscore table, once it's known, if it made it there
+    pub rank: Option<u8>,
+}
+
+impl Summary {
+    fn text(&self, restart_key: &str) -> String {
+        let seconds = self.millis / 1000;
+        let mut text = format!(
+            "game over: {}\napples: {}\ntime: {}:{:02}\ntiles: {}\ntop speed: {}",
+            self.ending,
+            self.apples,
+            seconds / 60,
+            seconds % 60,
+            self.tiles,
+            self.top_speed
+        );
+        if let Some(rank) = self.rank {
+            text.push_str(&format!("\nrank: #{rank} this year"));
+        }
+        text.push_str(&format!("\n\nPress {restart_key}\nto restart"));
+        text
+    }
+}
+
+/// Text shown on top of the board
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Overlay {
+    Intro,
+    Help,
+    GameOver(Summary),
+}
+
+pub struct Game {
+    pub status: GameStatus,
+    pub snake: Snake,
+    pub overlay: Option<Overlay>,
+    /// What's shown on the command line, if anything
+    pub cmdline: Option<String>,
+    settings: Arc<RwLock<Settings>>,
+    /// Steps queued up by motions, taken one per tick
+    moves: VecDeque<Direction>,
+    /// Where the head and tail were before the last tick, for animating the step
+    last_step: Option<(Position, Option<Position>)>,
+    /// Whether the game was paused by opening the command line,
+    /// and should continue when it is closed again
+    resume_after_cmdline: bool,
+    /// Ticks since the snake died, for its death animation and replay
+    dead_for: u32,
+    /// The snake before each of its last few steps, to replay after it dies
+    history: VecDeque<Snake>,
+    /// Time played, steps taken and fastest speed in the current game
+    millis: u32,
+    tiles: u32,
+    top_speed: u8,
+    /// The best game so far, to race against when the ghost is on
+    pub best: Option<Replay>,
+    ghost: Option<Ghost>,
+    /// The current game, so far
+    recording: Replay,
+    /// The seed of the current game, if it was chosen rather than random
+    pub board: Option<u64>,
+    /// Show the developer overlay
+    pub debug: bool,
+    name_entry: Option<NameEntry>,
+}
+
+/// How many ticks the death animation lasts
+const DEATH_TICKS: u32 = 8;
+/// How much of the game to replay after dying
+const REPLAY_MILLIS: u32 = 3000;
+/// How many ticks each step of the replay takes
+const SLOW_MOTION: u32 = 3;
+
+/// Whether the game does anything with a command in the given status,
+/// so the browser shouldn't handle its key as well
+pub fn handles(status: GameStatus, cmd: &Command) -> bool {
+    match cmd {
+        Command::Start => status != GameStatus::Playing,
+        Command::Pause | Command::Move(_) => status == GameStatus::Playing,
+        Command::Stop => matches!(status, GameStatus::Playing | GameStatus::Paused),
+        _ => true,
+    }
+}
+
+impl Game {
+    pub fn new(settings: Arc<RwLock<Settings>>) -> Self {
+        Self {
+            status: GameStatus::NotStarted,
+            snake: Snake::new(),
+            overlay: Some(Overlay::Intro),
+            cmdline: None,
+            settings,
+            moves: VecDeque::new(),
+            last_step: None,
+            resume_after_cmdline: false,
+            dead_for: 0,
+            history: VecDeque::new(),
+            millis: 0,
+            tiles: 0,
+            top_speed: 0,
+            best: None,
+            ghost: None,
+            recording: Replay::new(0, false),
+            board: None,
+            debug: false,
+            name_entry: None,
+        }
+    }
+
+    pub fn update(&mut self, input: Input) -> Vec<Effect> {
+        match input {
+            Input::Command(cmd) => self.command(cmd),
+            Input::Tick => self.tick(),
+            Input::Rank(rank) => {
+                if let Some(Overlay::GameOver(summary)) = &mut self.overlay {
+                    summary.rank = Some(rank);
+                }
+                vec![]
+            }
+            Input::AskName { prefill, reply } => {
+                // Too late to ask if a new game has started. Dropping the reply declines.
+                if self.status != GameStatus::GameOver {
+                    return vec![];
+                }
+                self.name_entry = Some(NameEntry::new(&prefill, reply));
+                vec![Effect::NameEntry(true)]
+            }
+        }
+    }
+
+    fn command(&mut self, cmd: Command) -> Vec<Effect> {
+        match cmd {
+            Command::Start if self.status == GameStatus::Playing => vec![],
+            Command::Start => self.start(false),
+            Command::Ex(ExCommand::Restart) => self.start(true),
+            Command::TogglePause if self.status == GameStatus::Playing => self.pause(),
+            // A stray tap shouldn't throw away the name being typed in
+            Command::TogglePause if self.name_entry.is_some() => vec![],
+            Command::TogglePause => self.start(false),
+            Command::Pause if self.status == GameStatus::Playing => self.pause(),
+            Command::Pause => vec![],
+            Command::Stop | Command::Ex(ExCommand::Quit) => {
+                self.resume_after_cmdline = false;
+                if matches!(self.status, GameStatus::Playing | GameStatus::Paused) {
+                    self.snake = self.snake.kill();
+                    self.game_over(Ending::Quit)
+                } else {
+                    vec![]
+                }
+            }
+            Command::Help | Command::Ex(ExCommand::Help) => {
+                self.resume_after_cmdline = false;
+                self.cmdline = None;
+                self.overlay = Some(Overlay::Help);
+                // Give the player time to read it
+                let mut effects = if self.status == GameStatus::Playing {
+                    self.pause()
+                } else {
+                    vec![]
+                };
+                effects.push(Effect::Help);
+                effects
+            }
+            Command::Move(_) if self.status != GameStatus::Playing => vec![],
+            Command::Move(motion) => {
+                // Expand from where the snake will be once it's done with earlier moves
+                let from = self
+                    .moves
+                    .iter()
+                    .fold(*self.snake.head(), |pos, dir| pos.step(*dir));
+                let heading = self.moves.back().copied().unwrap_or(self.snake.direction);
+                self.moves.extend(motion.directions(&from, heading));
+                vec![]
+            }
+            Command::EditCmdLine(line) => {
+                self.cmdline = Some(format!(":{line}"));
+                if self.status == GameStatus::Playing {
+                    self.resume_after_cmdline = true;
+                    self.pause()
+                } else {
+                    vec![]
+                }
+            }
+            Command::ExError(e) => {
+                self.resume_after_cmdline = false;
+                self.cmdline = Some(e.to_string());
+                vec![]
+            }
+            Command::Ex(ExCommand::Set(setting)) => {
+                let mut effects = vec![];
+                let mut settings = self.settings.write().unwrap();
+                match setting {
+                    Setting::Speed(speed) => settings.speed = speed,
+                    Setting::Wrap(wrap) => {
+                        settings.wrap = wrap;
+                        self.snake.wrap = wrap;
+                    }
+                    Setting::Relative(relative) => {
+                        settings.control_scheme = if relative {
+                            ControlScheme::Relative
+                        } else {
+                            ControlScheme::Absolute
+                        };
+                        effects.push(Effect::ControlScheme(settings.control_scheme));
+                    }
+                }
+                drop(settings);
+                effects.extend(self.close_cmdline());
+                effects
+            }
+            Command::Ex(ExCommand::Seed(seed)) => {
+                self.settings.write().unwrap().seed = Some(seed);
+                self.close_cmdline()
+            }
+            Command::CancelCmdLine => self.close_cmdline(),
+            Command::Insert(key) => {
+                let done = self.name_entry.as_mut().is_some_and(|entry| entry.key(key));
+                if done {
+                    self.name_entry = None;
+                    vec![Effect::NameEntry(false)]
+                } else {
+                    vec![]
+                }
+            }
+            Command::ToggleDebug => {
+                self.debug = !self.debug;
+                vec![]
+            }
+            Command::ToggleMute => {
+                let mut settings = self.settings.write().unwrap();
+                settings.muted = !settings.muted;
+                vec![Effect::Muted(settings.muted)]
+            }
+        }
+    }
+
+    fn tick(&mut self) -> Vec<Effect> {
+        if self.status == GameStatus::GameOver {
+            self.dead_for = self.dead_for.saturating_add(1);
+        }
+        if self.status != GameStatus::Playing {
+            return vec![];
+        }
+
+        let mut effects = vec![];
+        if let Some(d) = self.moves.pop_front() {
+            if d != self.snake.direction
+                && (self.snake.apple_count() == 0 || d != self.snake.direction.turn_180_degrees())
+            {
+                self.snake.direction = d;
+                self.recording.turns.push((self.tiles, d));
+                effects.push(Effect::Turned(d));
+            }
+        }
+
+        let settings = *self.settings.read().unwrap();
+        self.millis += settings.tick_millis();
+        self.top_speed = self.top_speed.max(settings.speed);
+        self.history.push_back(self.snake.clone());
+        while self.history.len() as u32 > REPLAY_MILLIS / settings.tick_millis() {
+            self.history.pop_front();
+        }
+
+        if let Some(ghost) = &mut self.ghost {
+            ghost.tick();
+        }
+
+        let collision = self.snake.collision();
+        let apples_before = self.snake.apple_count();
+        let head_from = *self.snake.head();
+        let (moved_snake, old_tail) = self.snake.move_along();
+        self.snake = moved_snake;
+        self.last_step = Some((head_from, old_tail));
+
+        if !self.snake.alive {
+            let ending = collision.map_or(Ending::Quit, Ending::Crashed);
+            return self.game_over(ending);
+        }
+        self.tiles += 1;
+
+        let apples = self.snake.apple_count();
+        if apples > apples_before {
+            effects.push(Effect::AteApple(apples));
+        }
+        effects.push(Effect::AppleCount(apples));
+        effects
+    }
+
+    /// Starts a new game, or continues the current one if it's paused and `restart` is false
+    fn start(&mut self, restart: bool) -> Vec<Effect> {
+        let mut effects = vec![];
+        if self.name_entry.take().is_some() {
+            effects.push(Effect::NameEntry(false));
+        }
+        if restart || self.status != GameStatus::Paused {
+            let settings = *self.settings.read().unwrap();
+            // Race the best game on its own board, unless asked for a different one
+            let best = self.best.as_ref().filter(|best| {
+                settings.ghost
+                    && best.wrap == settings.wrap
+                    && settings.seed.is_none_or(|seed| seed == best.seed)
+            });
+            let seed = best.map_or_else(|| settings.seed.unwrap_or_else(rand::random), |b| b.seed);
+            self.ghost = best.cloned().map(Ghost::new);
+            self.recording = Replay::new(seed, settings.wrap);
+            self.board = (best.is_some() || settings.seed.is_some()).then_some(seed);
+            self.snake = Snake::with_seed(seed);
+            self.snake.wrap = settings.wrap;
+            self.moves.clear();
+            self.history.clear();
+            self.millis = 0;
+            self.tiles = 0;
+            self.top_speed = settings.speed;
+            effects.push(Effect::NewGame);
+        }
+        self.last_step = None;
+
+        self.status = GameStatus::Playing;
+        self.resume_after_cmdline = false;
+        self.overlay = None;
+        self.cmdline = None;
+        effects.push(Effect::Status(self.status));
+        effects.push(Effect::AppleCount(self.snake.apple_count()));
+        effects
+    }
+
+    fn pause(&mut self) -> Vec<Effect> {
+        self.status = GameStatus::Paused;
+        self.last_step = None;
+        vec![Effect::Status(self.status)]
+    }
+
+    fn game_over(&mut self, ending: Ending) -> Vec<Effect> {
+        self.status = GameStatus::GameOver;
+        self.dead_for = 0;
+        self.overlay = Some(Overlay::GameOver(Summary {
+            apples: self.snake.apple_count(),
+            ending,
+            millis: self.millis,
+            tiles: self.tiles,
+            top_speed: self.top_speed,
+            rank: None,
+        }));
+
+        let apples = self.snake.apple_count();
+        let mut effects = vec![Effect::GameOver { apples, ending }];
+        if self
+            .best
+            .as_ref()
+            .map_or(apples > 0, |best| apples > best.apples)
+        {
+            self.recording.apples = apples;
+            self.best = Some(self.recording.clone());
+            effects.push(Effect::NewBest(self.recording.clone()));
+        }
+        effects
+    }
+
+    fn close_cmdline(&mut self) -> Vec<Effect> {
+        self.cmdline = None;
+        if self.resume_after_cmdline {
+            self.resume_after_cmdline = false;
+            self.status = GameStatus::Playing;
+            vec![Effect::Status(self.status)]
+        } else {
+            vec![]
+        }
+    }
+
+    /// The name being typed in for the highscore table, if it's being asked for
+    pub fn entered_name(&self) -> Option<&str> {
+        self.name_entry.as_ref().map(|entry| entry.name.as_str())
+    }
+
+    /// The next turn the snake will take, if any are queued up
+    pub fn pending_direction(&self) -> Option<Direction> {
+        self.moves.front().copied()
+    }
+
+    /// The snake part way through the slow motion replay of its last steps, over and over
+    fn replay(&self, progress: f64) -> (&Snake, Option<Tween>) {
+        let steps = self.history.len() + 1;
+        let replay_ticks = self.dead_for - DEATH_TICKS;
+        let step = (replay_ticks / SLOW_MOTION) as usize % steps;
+        let snake = self.history.get(step).unwrap_or(&self.snake);
+
+        let tween = step.checked_sub(1).map(|prev| {
+            let from = &self.history[prev];
+            Tween {
+                head_from: *from.head(),
+                // It didn't leave the cell if it grew
+                tail_from: (from.body.len() == snake.body.len()).then(|| *from.tail()),
+                progress: (f64::from(replay_ticks % SLOW_MOTION) + progress)
+                    / f64::from(SLOW_MOTION),
+            }
+        });
+        (snake, tween)
+    }
+
+    /// Everything which should be on the screen right now,
+    /// with the snake `progress` (0 to 1) of the way towards its next step
+    pub fn frame(&self, keymap: &Keymap, progress: f64) -> Frame<'_> {
+        let name_entry = self.name_entry.as_ref().map(|entry| Text {
+            content: entry.text(),
+            row: 2,
+        });
+        let text = name_entry.or_else(|| match self.overlay {
+            Some(Overlay::Intro) => Some(Text {
+                content: format!(
+                    "Press {}\nto start\n\nPress {} for\nhelp",
+                    keymap.first_key(Action::Start),
+                    keymap.first_key(Action::Help)
+                ),
+                row: 2,
+            }),
+            Some(Overlay::Help) => Some(Text {
+                content: keymap.help_text(self.settings.read().unwrap().control_scheme),
+                row: 2,
+            }),
+            Some(Overlay::GameOver(summary)) => Some(Text {
+                content: summary.text(keymap.first_key(Action::Start)),
+                row: 2,
+            }),
+            // The snake now covers the whole screen!
+            None if self.snake.target.is_none() => Some(Text {
+                content: String::from("💯 u crazy!! 💯"),
+                row: 8,
+            }),
+            None => None,
+        });
+
+        let board_hidden = matches!(self.overlay, Some(Overlay::Intro | Overlay::Help));
+        let tween = self
+            .last_step
+            .filter(|_| self.status == GameStatus::Playing)
+            .map(|(head_from, tail_from)| Tween {
+                head_from,
+                tail_from,
+                progress,
+            });
+        let dying = self.status == GameStatus::GameOver && self.dead_for < DEATH_TICKS;
+        let death = dying.then(|| (f64::from(self.dead_for) + progress) / f64::from(DEATH_TICKS));
+        let (snake, tween) = match self.status {
+            GameStatus::GameOver if !dying => self.replay(progress),
+            _ => (&self.snake, tween),
+        };
+
+        let ghost = self
+            .ghost
+            .as_ref()
+            .filter(|ghost| {
+                ghost.snake.alive
+                    && !board_hidden
+                    && matches!(self.status, GameStatus::Playing | GameStatus::Paused)
+            })
+            .map(|ghost| Layer {
+                snake: &ghost.snake,
+                tween: ghost
+                    .last_step
+                    .filter(|_| self.status == GameStatus::Playing)
+                    .map(|(head_from, tail_from)| Tween {
+                        head_from,
+                        tail_from,
+                        progress,
+                    }),
+            });
+
+        Frame {
+            snake: (!board_hidden).then_some(snake),
+            tween,
+            death,
+            ghost,
+            debug: None,
+            text,
+            cmdline: self.cmdline.as_deref(),
+            theme: self.settings.read().unwrap().theme.theme(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cmdline::ParseError;
+    use crate::name_entry::InsertKey;
+    use crate::vi::Motion;
+
+    fn new_game() -> Game {
+        Game::new(Arc::new(RwLock::new(Settings::default())))
+    }
+
+    fn playing_game() -> Game {
+        let mut game = new_game();
+        game.update(Input::Command(Command::Start));
+        game.snake.target = Some(Position { x: 200.0, y: 200.0 });
+        game
+    }
+
+    #[test]
+    fn it_starts_not_started() {
+        assert_eq!(GameStatus::NotStarted, new_game().status);
+    }
+
+    #[test]
+    fn start_should_start_playing_and_hide_intro() {
+        let mut game = new_game();
+        let effects = game.update(Input::Command(Command::Start));
+
+        assert_eq!(GameStatus::Playing, game.status);
+        assert!(effects.contains(&Effect::Status(GameStatus::Playing)));
+        assert_eq!(None, game.overlay);
+    }
+
+    #[test]
+    fn tick_should_do_nothing_unless_playing() {
+        let mut game = new_game();
+        assert!(game.update(Input::Tick).is_empty());
+        assert_eq!(Position { x: 0.0, y: 0.0 }, *game.snake.head());
+    }
+
+    #[test]
+    fn tick_should_move_snake_while_playing() {
+        let mut game = playing_game();
+        let effects = game.update(Input::Tick);
+
+        assert_eq!(Position { x: 25.0, y: 0.0 }, *game.snake.head());
+        assert_eq!(vec![Effect::AppleCount(0)], effects);
+    }
+
+    #[test]
+    fn stop_should_end_the_game() {
+        let mut game = playing_game();
+        let effects = game.update(Input::Command(Command::Stop));
+
+        assert_eq!(GameStatus::GameOver, game.status);
+        assert_eq!(
+            vec![Effect::GameOver {
+                apples: 0,
+                ending: Ending::Quit
+            }],
+            effects
+        );
+        assert!(matches!(
+            game.overlay,
+            Some(Overlay::GameOver(Summary {
+                apples: 0,
+                ending: Ending::Quit,
+                ..
+            }))
+        ));
+    }
+
+    #[test]
+    fn stop_should_do_nothing_before_the_game_started() {
+        let mut game = new_game();
+        assert!(game.update(Input::Command(Command::Stop)).is_empty());
+        assert_eq!(GameStatus::NotStarted, game.status);
+    }
+
+    #[test]
+    fn crashing_should_end_the_game() {
+        let mut game = playing_game();
+        game.snake.direction = Direction::Up;
+        let effects = game.update(Input::Tick);
+
+        assert_eq!(GameStatus::GameOver, game.status);
+        assert_eq!(
+            vec![Effect::GameOver {
+                apples: 0,
+                ending: Ending::Crashed(Collision::Wall)
+            }],
+            effects
+        );
+    }
+
+    #[test]
+    fn toggle_pause_should_pause_and_resume_the_same_game() {
+        let mut game = playing_game();
+        game.update(Input::Tick);
+
+        game.update(Input::Command(Command::TogglePause));
+        assert_eq!(GameStatus::Paused, game.status);
+        assert!(game.update(Input::Tick).is_empty());
+
+        game.update(Input::Command(Command::TogglePause));
+        assert_eq!(GameStatus::Playing, game.status);
+        assert_eq!(Position { x: 25.0, y: 0.0 }, *game.snake.head());
+    }
+
+    #[test]
+    fn start_after_game_over_should_start_a_new_game() {
+        let mut game = playing_game();
+        game.update(Input::Tick);
+        game.update(Input::Command(Command::Stop));
+
+        game.update(Input::Command(Command::Start));
+
+        assert_eq!(GameStatus::Playing, game.status);
+        assert!(game.snake.alive);
+        assert_eq!(Position { x: 0.0, y: 0.0 }, *game.snake.head());
+    }
+
+    #[test]
+    fn restart_should_start_a_new_game_even_when_paused() {
+        let mut game = playing_game();
+        game.update(Input::Tick);
+        game.update(Input::Command(Command::Pause));
+
+        game.update(Input::Command(Command::Ex(ExCommand::Restart)));
+
+        assert_eq!(GameStatus::Playing, game.status);
+        assert_eq!(Position { x: 0.0, y: 0.0 }, *game.snake.head());
+    }
+
+    #[test]
+    fn help_should_be_shown_in_any_state() {
+        let mut game = new_game();
+        game.update(Input::Command(Command::Help));
+        assert_eq!(Some(Overlay::Help), game.overlay);
+    }
+
+    #[test]
+    fn help_should_pause_and_start_should_hide_it() {
+        let mut game = playing_game();
+        game.update(Input::Command(Command::Help));
+        assert_eq!(GameStatus::Paused, game.status);
+
+        game.update(Input::Command(Command::Start));
+        assert_eq!(GameStatus::Playing, game.status);
+        assert_eq!(None, game.overlay);
+    }
+
+    #[test]
+    fn moves_should_be_taken_one_per_tick() {
+        let mut game = playing_game();
+        game.update(Input::Command(Command::Move(Motion::Steps(
+            Direction::Down,
+            2,
+        ))));
+
+        game.update(Input::Tick);
+        assert_eq!(Position { x: 0.0, y: 25.0 }, *game.snake.head());
+        game.update(Input::Tick);
+        assert_eq!(Position { x: 0.0, y: 50.0 }, *game.snake.head());
+        assert_eq!(Direction::Down, game.snake.direction);
+    }
+
+    #[test]
+    fn moves_should_be_ignored_unless_playing() {
+        let mut game = new_game();
+        game.update(Input::Command(Command::Move(Motion::Steps(
+            Direction::Down,
+            1,
+        ))));
+        game.update(Input::Command(Command::Start));
+        game.update(Input::Tick);
+
+        assert_eq!(Direction::Right, game.snake.direction);
+    }
+
+    #[test]
+    fn opening_cmdline_should_pause_and_closing_it_should_resume() {
+        let mut game = playing_game();
+
+        game.update(Input::Command(Command::EditCmdLine(String::new())));
+        assert_eq!(GameStatus::Paused, game.status);
+        assert_eq!(Some(":"), game.cmdline.as_deref());
+
+        game.update(Input::Command(Command::CancelCmdLine));
+        assert_eq!(GameStatus::Playing, game.status);
+        assert_eq!(None, game.cmdline);
+    }
+
+    #[test]
+    fn cmdline_error_should_stay_paused() {
+        let mut game = playing_game();
+        game.update(Input::Command(Command::EditCmdLine(String::from("x"))));
+
+        game.update(Input::Command(Command::ExError(ParseError::NotACommand(
+            String::from("x"),
+        ))));
+
+        assert_eq!(GameStatus::Paused, game.status);
+        assert_eq!(
+            Some("E492: Not an editor command: x"),
+            game.cmdline.as_deref()
+        );
+    }
+
+    #[test]
+    fn closing_cmdline_opened_before_playing_should_just_clear_it() {
+        let mut game = new_game();
+        game.update(Input::Command(Command::EditCmdLine(String::new())));
+
+        let effects = game.update(Input::Command(Command::CancelCmdLine));
+
+        assert_eq!(GameStatus::NotStarted, game.status);
+        assert!(effects.is_empty());
+        assert_eq!(None, game.cmdline);
+    }
+
+    #[test]
+    fn set_wrap_should_apply_to_current_snake() {
+        let mut game = playing_game();
+        game.update(Input::Command(Command::Ex(ExCommand::Set(Setting::Wrap(
+            true,
+        )))));
+
+        assert!(game.snake.wrap);
+        assert!(game.settings.read().unwrap().wrap);
+    }
+
+    #[test]
+    fn moves_should_only_be_handled_while_playing() {
+        let step = || Command::Move(Motion::Steps(Direction::Up, 1));
+        assert!(handles(GameStatus::Playing, &step()));
+        assert!(!handles(GameStatus::NotStarted, &step()));
+        assert!(!handles(GameStatus::Playing, &Command::Start));
+        assert!(handles(GameStatus::GameOver, &Command::Help));
+    }
+
+    #[test]
+    fn set_relative_should_change_the_control_scheme() {
+        let mut game = new_game();
+        let effects = game.update(Input::Command(Command::Ex(ExCommand::Set(
+            Setting::Relative(true),
+        ))));
+
+        assert_eq!(
+            vec![Effect::ControlScheme(ControlScheme::Relative)],
+            effects
+        );
+        assert_eq!(
+            ControlScheme::Relative,
+            game.settings.read().unwrap().control_scheme
+        );
+    }
+
+    #[test]
+    fn seed_should_be_used_for_the_next_game() {
+        let mut game = new_game();
+        game.update(Input::Command(Command::Ex(ExCommand::Seed(42))));
+        game.update(Input::Command(Command::Start));
+
+        assert_eq!(Snake::with_seed(42).target, game.snake.target);
+        assert_eq!(Some(42), game.board);
+    }
+
+    #[test]
+    fn random_games_should_have_no_board() {
+        let game = playing_game();
+        assert_eq!(None, game.board);
+    }
+
+    #[test]
+    fn frame_should_hide_the_board_behind_intro_and_help() {
+        let keymap = Keymap::default();
+        let mut game = new_game();
+        assert!(game.frame(&keymap, 1.0).snake.is_none());
+
+        game.update(Input::Command(Command::Start));
+        assert!(game.frame(&keymap, 1.0).snake.is_some());
+
+        game.update(Input::Command(Command::Help));
+        let frame = game.frame(&keymap, 1.0);
+        assert!(frame.snake.is_none());
+        assert!(frame.text.is_some_and(|t| t.content.contains("help: ?")));
+    }
+
+    #[test]
+    fn frame_should_show_summary_over_the_board_after_game_over() {
+        let keymap = Keymap::default();
+        let mut game = playing_game();
+        game.update(Input::Command(Command::Stop));
+
+        let frame = game.frame(&keymap, 1.0);
+        assert!(frame.snake.is_some());
+        let text = frame.text.unwrap().content;
+        assert!(text.starts_with("game over: quit\napples: 0\n"));
+        assert!(text.ends_with("Press <space>\nto restart"));
+    }
+
+    #[test]
+    fn summary_should_count_time_tiles_and_speed() {
+        let mut game = playing_game();
+        game.update(Input::Tick);
+        game.update(Input::Tick);
+        game.settings.write().unwrap().speed = 9;
+        game.update(Input::Tick);
+        game.update(Input::Command(Command::Stop));
+
+        let Some(Overlay::GameOver(summary)) = game.overlay else {
+            panic!("no summary after game over");
+        };
+        assert_eq!(3, summary.tiles);
+        assert_eq!(300 + 300 + 100, summary.millis);
+        assert_eq!(9, summary.top_speed);
+        assert_eq!(None, summary.rank);
+    }
+
+    #[test]
+    fn summary_should_show_rank_once_known() {
+        let keymap = Keymap::default();
+        let mut game = playing_game();
+        game.update(Input::Command(Command::Stop));
+        game.update(Input::Rank(3));
+
+        let text = game.frame(&keymap, 0.0).text.unwrap().content;
+        assert!(text.contains("rank: #3 this year"));
+    }
+
+    #[test]
+    fn start_should_restart_from_game_over() {
+        let mut game = playing_game();
+        game.update(Input::Tick);
+        game.update(Input::Command(Command::Stop));
+
+        let effects = game.update(Input::Command(Command::Start));
+
+        assert!(effects.contains(&Effect::NewGame));
+        assert_eq!(GameStatus::Playing, game.status);
+        assert_eq!(None, game.overlay);
+    }
+
+    #[test]
+    fn frame_should_tween_the_last_step_while_playing() {
+        let keymap = Keymap::default();
+        let mut game = playing_game();
+        game.update(Input::Tick);
+
+        let tween = game.frame(&keymap, 0.5).tween.unwrap();
+        assert_eq!(Position { x: 0.0, y: 0.0 }, tween.head_from);
+        assert_eq!(Some(Position { x: 0.0, y: 0.0 }), tween.tail_from);
+        assert_eq!(0.5, tween.progress);
+
+        game.update(Input::Command(Command::Pause));
+        assert_eq!(None, game.frame(&keymap, 0.5).tween);
+    }
+
+    #[test]
+    fn frame_should_animate_death_until_it_is_over() {
+        let keymap = Keymap::default();
+        let mut game = playing_game();
+        assert_eq!(None, game.frame(&keymap, 0.5).death);
+
+        game.update(Input::Command(Command::Stop));
+        assert_eq!(Some(0.0), game.frame(&keymap, 0.0).death);
+
+        for _ in 0..DEATH_TICKS {
+            game.update(Input::Tick);
+        }
+        assert_eq!(None, game.frame(&keymap, 0.5).death);
+    }
+
+    #[test]
+    fn frame_should_replay_the_last_steps_slowly_after_dying() {
+        let keymap = Keymap::default();
+        let mut game = playing_game();
+        game.update(Input::Tick);
+        game.update(Input::Tick);
+        game.update(Input::Command(Command::Stop));
+        for _ in 0..DEATH_TICKS {
+            game.update(Input::Tick);
+        }
+
+        // Starts from the oldest step...
+        let frame = game.frame(&keymap, 0.5);
+        assert_eq!(Position { x: 0.0, y: 0.0 }, *frame.snake.unwrap().head());
+        assert_eq!(None, frame.tween);
+
+        // ...and takes a few ticks for each step
+        for _ in 0..SLOW_MOTION {
+            game.update(Input::Tick);
+        }
+        let frame = game.frame(&keymap, 0.5);
+        assert_eq!(Position { x: 25.0, y: 0.0 }, *frame.snake.unwrap().head());
+        assert_eq!(0.5 / f64::from(SLOW_MOTION), frame.tween.unwrap().progress);
+    }
+
+    #[test]
+    fn beating_the_best_game_should_record_it() {
+        let mut game = playing_game();
+        game.snake.target = Some(Position { x: 25.0, y: 0.0 });
+        game.update(Input::Tick);
+        game.update(Input::Command(Command::Move(Motion::Steps(
+            Direction::Down,
+            1,
+        ))));
+        game.update(Input::Tick);
+
+        let effects = game.update(Input::Command(Command::Stop));
+
+        let Some(Effect::NewBest(replay)) = effects.last() else {
+            panic!("no new best in {effects:?}");
+        };
+        assert_eq!(1, replay.apples);
+        assert_eq!(vec![(1, Direction::Down)], replay.turns);
+        assert_eq!(Some(replay), game.best.as_ref());
+    }
+
+    #[test]
+    fn games_without_apples_should_not_be_the_best() {
+        let mut game = playing_game();
+        let effects = game.update(Input::Command(Command::Stop));
+        assert!(!effects.iter().any(|e| matches!(e, Effect::NewBest(_))));
+    }
+
+    #[test]
+    fn ghost_should_race_on_the_best_games_seed() {
+        let keymap = Keymap::default();
+        let mut game = new_game();
+        game.settings.write().unwrap().ghost = true;
+        game.best = Some(Replay {
+            apples: 3,
+            ..Replay::new(42, false)
+        });
+
+        game.update(Input::Command(Command::Start));
+        assert_eq!(42, game.recording.seed);
+        assert_eq!(Some(42), game.board);
+        game.update(Input::Tick);
+
+        let frame = game.frame(&keymap, 0.5);
+        let ghost = frame.ghost.unwrap();
+        assert_eq!(Position { x: 25.0, y: 0.0 }, *ghost.snake.head());
+        assert_eq!(0.5, ghost.tween.unwrap().progress);
+    }
+
+    #[test]
+    fn ghost_should_only_show_when_turned_on() {
+        let keymap = Keymap::default();
+        let mut game = new_game();
+        game.best = Some(Replay::new(42, false));
+
+        game.update(Input::Command(Command::Start));
+
+        assert!(game.frame(&keymap, 0.0).ghost.is_none());
+    }
+
+    fn ask_name(game: &mut Game) -> (Vec<Effect>, oneshot::Receiver<Option<String>>) {
+        let (reply, answer) = oneshot::channel();
+        let effects = game.update(Input::AskName {
+            prefill: String::from("snakey"),
+            reply,
+        });
+        (effects, answer)
+    }
+
+    #[test]
+    fn name_entry_should_show_on_the_board_and_reply_with_the_name() {
+        let keymap = Keymap::default();
+        let mut game = playing_game();
+        game.update(Input::Command(Command::Stop));
+
+        let (effects, mut answer) = ask_name(&mut game);
+        assert_eq!(vec![Effect::NameEntry(true)], effects);
+        game.update(Input::Command(Command::Insert(InsertKey::Char('!'))));
+        let text = game.frame(&keymap, 0.0).text.unwrap().content;
+        assert!(text.contains("name: snakey!_"));
+
+        let effects = game.update(Input::Command(Command::Insert(InsertKey::Enter)));
+        assert_eq!(vec![Effect::NameEntry(false)], effects);
+        assert_eq!(Ok(Some(Some(String::from("snakey!")))), answer.try_recv());
+    }
+
+    #[test]
+    fn name_should_not_be_asked_for_once_a_new_game_started() {
+        let mut game = playing_game();
+        let (effects, mut answer) = ask_name(&mut game);
+
+        assert!(effects.is_empty());
+        assert!(answer.try_recv().is_err());
+    }
+
+    #[test]
+    fn restarting_should_decline_the_name_entry() {
+        let mut game = playing_game();
+        game.update(Input::Command(Command::Stop));
+        let (_, mut answer) = ask_name(&mut game);
+
+        let effects = game.update(Input::Command(Command::Start));
+
+        assert!(effects.contains(&Effect::NameEntry(false)));
+        assert!(answer.try_recv().is_err());
+    }
+
+    #[test]
+    fn tapping_should_keep_the_name_entry() {
+        let mut game = playing_game();
+        game.update(Input::Command(Command::Stop));
+        let (_, mut answer) = ask_name(&mut game);
+
+        let effects = game.update(Input::Command(Command::TogglePause));
+
+        assert!(effects.is_empty());
+        assert_eq!(Some("snakey"), game.entered_name());
+        assert_eq!(Ok(None), answer.try_recv());
+    }
+
+    #[test]
+    fn eating_an_apple_should_be_reported() {
+        let mut game = playing_game();
+        game.snake.target = Some(Position { x: 25.0, y: 0.0 });
+
+        let effects = game.update(Input::Tick);
+
+        assert_eq!(vec![Effect::AteApple(1), Effect::AppleCount(1)], effects);
+    }
+
+    #[test]
+    fn turning_should_be_reported() {
+        let mut game = playing_game();
+        game.update(Input::Command(Command::Move(Motion::Steps(
+            Direction::Down,
+            1,
+        ))));
+
+        let effects = game.update(Input::Tick);
+
+        assert_eq!(Some(&Effect::Turned(Direction::Down)), effects.first());
+    }
+
+    #[test]
+    fn toggle_mute_should_flip_the_setting() {
+        let mut game = new_game();
+        assert_eq!(
+            vec![Effect::Muted(true)],
+            game.update(Input::Command(Command::ToggleMute))
+        );
+        assert_eq!(
+            vec![Effect::Muted(false)],
+            game.update(Input::Command(Command::ToggleMute))
+        );
+    }
+}
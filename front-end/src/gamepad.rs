@@ -0,0 +1,277 @@
+//! Converts gamepad buttons and sticks to commands
+//!
+//! Gamepads don't fire events when buttons are pressed, so they are polled instead.
+use std::cell::Cell;
+use std::pin::Pin;
+use std::rc::Rc;
+use std::task::{Context, Poll};
+
+use futures::channel::mpsc;
+use futures::stream::Stream;
+use gloo_events::EventListener;
+use gloo_timers::callback::Interval;
+use gloo_utils::window;
+use wasm_bindgen::JsCast;
+use web_sys::{Gamepad, GamepadButton, GamepadEvent};
+
+use crate::vi::{Command, Motion};
+use crate::Direction;
+
+const POLL_MILLIS: u32 = 16;
+/// How far the stick must be pushed before it counts as a direction
+const STICK_DEADZONE: f64 = 0.5;
+
+// Button indices in the "standard" gamepad mapping
+const SELECT: u32 = 8;
+const START: u32 = 9;
+const DPAD: [(u32, Direction); 4] = [
+    (12, Direction::Up),
+    (13, Direction::Down),
+    (14, Direction::Left),
+    (15, Direction::Right),
+];
+
+pub struct Gamepads {
+    pub receiver: mpsc::UnboundedReceiver<Command>,
+    _listeners: [EventListener; 2],
+    _poller: Interval,
+}
+
+/// The parts of a gamepad's state which the game cares about
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+struct PadState {
+    direction: Option<Direction>,
+    start: bool,
+    select: bool,
+}
+
+impl PadState {
+    fn from_gamepad(pad: &Gamepad) -> PadState {
+        let buttons = pad.buttons();
+        let pressed = |i| {
+            buttons
+                .get(i)
+                .dyn_into::<GamepadButton>()
+                .is_ok_and(|b| b.pressed())
+        };
+        let axes = pad.axes();
+        let axis = |i| axes.get(i).as_f64().unwrap_or(0.0);
+
+        PadState {
+            direction: DPAD
+                .into_iter()
+                .find(|(i, _)| pressed(*i))
+                .map(|(_, dir)| dir)
+                .or_else(|| stick_direction(axis(0), axis(1))),
+            start: pressed(START),
+            select: pressed(SELECT),
+        }
+    }
+
+    /// Combines the state of several gamepads, so any of them can be used
+    fn merge(self, other: PadState) -> PadState {
+        PadState {
+            direction: self.direction.or(other.direction),
+            start: self.start || other.start,
+            select: self.select || other.select,
+        }
+    }
+
+    /// The commands triggered by going from the previous state to this one
+    fn commands(&self, prev: &PadState) -> Vec<Command> {
+        let mut commands = Vec::new();
+
+        match self.direction {
+            Some(dir) if self.direction != prev.direction => {
+                commands.push(Command::Move(Motion::Steps(dir, 1)));
+            }
+            _ => {}
+        }
+        if self.start && !prev.start {
+            commands.push(Command::TogglePause);
+        }
+        if self.select && !prev.select {
+            commands.push(Command::Help);
+        }
+
+        commands
+    }
+}
+
+fn stick_direction(x: f64, y: f64) -> Option<Direction> {
+    if x.abs() < STICK_DEADZONE && y.abs() < STICK_DEADZONE {
+        None
+    } else if x.abs() > y.abs() {
+        Some(if x < 0.0 {
+            Direction::Left
+        } else {
+            Direction::Right
+        })
+    } else {
+        Some(if y < 0.0 {
+            Direction::Up
+        } else {
+            Direction::Down
+        })
+    }
+}
+
+fn read_gamepads() -> PadState {
+    let Ok(pads) = window().navigator().get_gamepads() else {
+        return PadState::default();
+    };
+
+    pads.iter()
+        .filter_map(|pad| pad.dyn_into::<Gamepad>().ok())
+        .filter(Gamepad::connected)
+        .map(|pad| PadState::from_gamepad(&pad))
+        .fold(PadState::default(), PadState::merge)
+}
+
+impl Gamepads {
+    pub fn new() -> Self {
+        let (sender, receiver) = mpsc::unbounded();
+        let connected = Rc::new(Cell::new(0_u32));
+
+        let on_connected = {
+            let connected = Rc::clone(&connected);
+            move |event: &web_sys::Event| {
+                connected.set(connected.get() + 1);
+                let event = event.dyn_ref::<GamepadEvent>().unwrap();
+                log::info!("Gamepad connected: {:?}", event.gamepad().map(|p| p.id()));
+            }
+        };
+
+        let on_disconnected = {
+            let connected = Rc::clone(&connected);
+            let sender = sender.clone();
+            move |event: &web_sys::Event| {
+                connected.set(connected.get().saturating_sub(1));
+                let event = event.dyn_ref::<GamepadEvent>().unwrap();
+                log::info!(
+                    "Gamepad disconnected: {:?}",
+                    event.gamepad().map(|p| p.id())
+                );
+
+                // Don't let the snake crash while the player is looking for the cable
+                sender.unbounded_send(Command::Pause).unwrap();
+            }
+        };
+
+        let poller = {
+            let mut prev = PadState::default();
+            Interval::new(POLL_MILLIS, move || {
+                if connected.get() == 0 {
+                    return;
+                }
+
+                let state = read_gamepads();
+                for cmd in state.commands(&prev) {
+                    sender.unbounded_send(cmd).unwrap();
+                }
+                prev = state;
+            })
+        };
+
+        Self {
+            receiver,
+            _listeners: [
+                EventListener::new(&window(), "gamepadconnected", on_connected),
+                EventListener::new(&window(), "gamepaddisconnected", on_disconnected),
+            ],
+            _poller: poller,
+        }
+    }
+}
+
+impl Stream for Gamepads {
+    type Item = Command;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        Pin::new(&mut self.receiver).poll_next(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stick_inside_deadzone_should_have_no_direction() {
+        assert_eq!(None, stick_direction(0.2, -0.3));
+    }
+
+    #[test]
+    fn stick_should_point_along_the_axis_pushed_furthest() {
+        assert_eq!(Some(Direction::Left), stick_direction(-0.9, 0.4));
+        assert_eq!(Some(Direction::Down), stick_direction(0.1, 0.8));
+    }
+
+    #[test]
+    fn new_direction_should_move() {
+        let prev = PadState::default();
+        let state = PadState {
+            direction: Some(Direction::Up),
+            ..PadState::default()
+        };
+
+        assert!(matches!(
+            state.commands(&prev).as_slice(),
+            [Command::Move(Motion::Steps(Direction::Up, 1))]
+        ));
+    }
+
+    #[test]
+    fn held_buttons_should_only_trigger_once() {
+        let state = PadState {
+            direction: Some(Direction::Up),
+            start: true,
+            select: true,
+        };
+        assert!(state.commands(&state).is_empty());
+    }
+
+    #[test]
+    fn start_should_toggle_pause() {
+        let state = PadState {
+            start: true,
+            ..PadState::default()
+        };
+        assert!(matches!(
+            state.commands(&PadState::default()).as_slice(),
+            [Command::TogglePause]
+        ));
+    }
+
+    #[test]
+    fn select_should_show_help() {
+        let state = PadState {
+            select: true,
+            ..PadState::default()
+        };
+        assert!(matches!(
+            state.commands(&PadState::default()).as_slice(),
+            [Command::Help]
+        ));
+    }
+
+    #[test]
+    fn merge_should_combine_pads() {
+        let a = PadState {
+            start: true,
+            ..PadState::default()
+        };
+        let b = PadState {
+            direction: Some(Direction::Left),
+            ..PadState::default()
+        };
+        assert_eq!(
+            PadState {
+                direction: Some(Direction::Left),
+                start: true,
+                select: false
+            },
+            a.merge(b)
+        );
+    }
+}
@@ -0,0 +1,108 @@
+//! Records games so the best one can be raced against as a "ghost" snake
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::JsValue;
+
+use crate::snake::{Direction, Position, Snake};
+use crate::storage;
+
+const PERSONAL_BEST_KEY: &str = "personal-best";
+
+/// Everything needed to play a game again exactly the way it went
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Replay {
+    pub seed: u64,
+    pub wrap: bool,
+    pub apples: u8,
+    /// The tick on which the snake turned, and where to
+    pub turns: Vec<(u32, Direction)>,
+}
+
+impl Replay {
+    pub fn new(seed: u64, wrap: bool) -> Self {
+        Self {
+            seed,
+            wrap,
+            apples: 0,
+            turns: vec![],
+        }
+    }
+
+    pub fn load_personal_best() -> Option<Self> {
+        storage::load(PERSONAL_BEST_KEY)
+    }
+
+    pub fn save_as_personal_best(&self) -> Result<(), JsValue> {
+        storage::save(PERSONAL_BEST_KEY, self)
+    }
+}
+
+/// A snake playing back a replay, one step per tick. It only exists to be looked at,
+/// so it can't collide with the player's snake.
+#[derive(Clone, Debug)]
+pub struct Ghost {
+    replay: Replay,
+    pub snake: Snake,
+    ticks: u32,
+    /// Where the head and tail were before the last tick, for animating the step
+    pub last_step: Option<(Position, Option<Position>)>,
+}
+
+impl Ghost {
+    pub fn new(replay: Replay) -> Self {
+        let mut snake = Snake::with_seed(replay.seed);
+        snake.wrap = replay.wrap;
+        Self {
+            replay,
+            snake,
+            ticks: 0,
+            last_step: None,
+        }
+    }
+
+    pub fn tick(&mut self) {
+        if !self.snake.alive {
+            self.last_step = None;
+            return;
+        }
+        if let Some((_, dir)) = self.replay.turns.iter().find(|(t, _)| *t == self.ticks) {
+            self.snake.direction = *dir;
+        }
+
+        let head_from = *self.snake.head();
+        let (moved_snake, old_tail) = self.snake.move_along();
+        self.snake = moved_snake;
+        self.last_step = Some((head_from, old_tail));
+        self.ticks += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ghost_should_turn_on_the_recorded_ticks() {
+        let mut replay = Replay::new(1, false);
+        replay.turns = vec![(1, Direction::Down), (3, Direction::Right)];
+        let mut ghost = Ghost::new(replay);
+
+        for _ in 0..4 {
+            ghost.tick();
+        }
+
+        assert_eq!(Position { x: 50.0, y: 50.0 }, *ghost.snake.head());
+    }
+
+    #[test]
+    fn ghost_should_stop_once_it_crashes() {
+        let mut replay = Replay::new(1, false);
+        replay.turns = vec![(0, Direction::Up)];
+        let mut ghost = Ghost::new(replay);
+
+        ghost.tick();
+        ghost.tick();
+
+        assert!(!ghost.snake.alive);
+        assert_eq!(None, ghost.last_step);
+    }
+}
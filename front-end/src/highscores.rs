@@ -1,25 +1,135 @@
 //! Fetch, render and submit highscores
-use gloo_dialogs::prompt;
+use std::cell::RefCell;
+use std::future::Future;
+
 use gloo_utils::document;
-use highscore_types::HighScoreDto;
+use highscore_types::{HighScoreDto, Period, RankDto};
 use js_sys::Error;
-use time::{format_description::well_known::Rfc3339, OffsetDateTime};
-use wasm_bindgen::{JsCast, JsValue};
-use web_sys::HtmlElement;
+use time::OffsetDateTime;
+use wasm_bindgen::JsValue;
+use web_sys::{Document, Element};
 
+use crate::name_entry;
+use crate::outbox::{Delivery, Outbox};
 use crate::services::highscore_api::HighScoreApi;
 
-trait HighScoreDtoExt {
-    fn to_table_row(&self) -> String;
+/// Columns of the highscore tables
+const COLUMNS: usize = 4;
+
+/// Text for each column of a highscore's row: rank, name, score and date
+fn row_cells(rank: usize, highscore: &HighScoreDto) -> [String; COLUMNS] {
+    [
+        rank.to_string(),
+        highscore.user_name.clone(),
+        highscore.score.to_string(),
+        highscore
+            .timestamp
+            .map(|t| t.date().to_string())
+            .unwrap_or_default(),
+    ]
+}
+
+/// A highscore to point out in the tables
+#[derive(Clone, Debug, PartialEq)]
+pub enum Highlight {
+    /// The player just submitted it
+    Own(HighScoreDto),
+    /// Someone else just submitted it
+    Entered(HighScoreDto),
+}
+
+impl Highlight {
+    fn highscore(&self) -> &HighScoreDto {
+        match self {
+            Highlight::Own(highscore) | Highlight::Entered(highscore) => highscore,
+        }
+    }
+
+    fn class(&self) -> &'static str {
+        match self {
+            Highlight::Own(_) => "own",
+            Highlight::Entered(_) => "entered",
+        }
+    }
+}
+
+/// Whether both are the same highscore, as far as the tables can tell.
+/// Timestamps are only set by the api, so they're compared when both have one.
+pub fn is_same(highscore: &HighScoreDto, other: Option<&HighScoreDto>) -> bool {
+    other.is_some_and(|other| {
+        other.user_name == highscore.user_name
+            && other.score == highscore.score
+            && match (other.timestamp, highscore.timestamp) {
+                (Some(a), Some(b)) => a == b,
+                _ => true,
+            }
+    })
 }
 
-impl HighScoreDtoExt for HighScoreDto {
-    fn to_table_row(&self) -> String {
-        format!(
-            "<tr><td>{}</td><td>{}</td></tr>",
-            self.user_name, self.score
-        )
+fn table_row(doc: &Document, cells: &[String], class: &str) -> Result<Element, JsValue> {
+    let row = doc.create_element("tr")?;
+    row.set_class_name(class);
+    for text in cells {
+        let cell = doc.create_element("td")?;
+        // Never as html, since names come from other players
+        cell.set_text_content(Some(text));
+        row.append_child(&cell)?;
     }
+    Ok(row)
+}
+
+/// A row spanning the whole table, for when there are no highscores to show
+fn message_row(doc: &Document, message: &str, class: &str) -> Result<Element, JsValue> {
+    let row = doc.create_element("tr")?;
+    row.set_class_name(class);
+    let cell = doc.create_element("td")?;
+    cell.set_attribute("colspan", &COLUMNS.to_string())?;
+    cell.set_text_content(Some(message));
+    row.append_child(&cell)?;
+    Ok(row)
+}
+
+/// Replaces the rows of a table with the highscores, or with what went wrong fetching them.
+/// They're ranked from `first_rank` and down.
+pub fn set_table(
+    doc: &Document,
+    selector: &str,
+    highscores: Result<Vec<HighScoreDto>, anyhow::Error>,
+    first_rank: usize,
+    highlight: Option<&Highlight>,
+) -> Result<(), JsValue> {
+    let tbody = doc
+        .query_selector(selector)?
+        .ok_or_else(|| Error::new(&format!("Cant find highscore table {selector}")))?;
+    tbody.set_text_content(None);
+
+    let highscores = match highscores {
+        Ok(highscores) => highscores,
+        Err(err) => {
+            log::error!("Error fetching highscores for {selector}: {err:?}");
+            let row = message_row(doc, "Failed to fetch highscores 😩", "error")?;
+            tbody.append_child(&row)?;
+            return Ok(());
+        }
+    };
+
+    if highscores.is_empty() {
+        let row = message_row(doc, "No highscores yet. Be the first!", "empty")?;
+        tbody.append_child(&row)?;
+    }
+    // Only highlight the first match, in case the same name got the same score before
+    let mut highlight = highlight;
+    for (i, highscore) in highscores.iter().enumerate() {
+        let class = if let Some(h) = highlight.filter(|h| is_same(highscore, Some(h.highscore()))) {
+            highlight = None;
+            h.class()
+        } else {
+            ""
+        };
+        let row = table_row(doc, &row_cells(first_rank + i, highscore), class)?;
+        tbody.append_child(&row)?;
+    }
+    Ok(())
 }
 
 fn start_of_year() -> Result<OffsetDateTime, anyhow::Error> {
@@ -27,97 +137,208 @@ fn start_of_year() -> Result<OffsetDateTime, anyhow::Error> {
     // See https://github.com/rust-lang/rust/issues/48564
     let millis = js_sys::Date::now() as i64;
     let now = OffsetDateTime::from_unix_timestamp(millis / 1000)?;
-    let jan_first = format!("{}-01-01T00:00:00Z", now.year());
-    Ok(OffsetDateTime::parse(&jan_first, &Rfc3339)?)
+    Period::Year
+        .start(now)
+        .ok_or_else(|| anyhow::anyhow!("Unable to find the start of {}", now.year()))
 }
 
-pub async fn fetch_and_set(client: &HighScoreApi) -> Result<(), JsValue> {
+/// Fills the highscore tables, highlighting a highscore which was just submitted
+pub async fn fetch_and_set(
+    client: &HighScoreApi,
+    highlight: Option<&Highlight>,
+) -> Result<(), JsValue> {
     let dom = document();
 
     let topten_alltime_fut = client.top_ten(None);
 
     if let Ok(start_of_year) = start_of_year() {
-        let top_yearly_html = client.top_ten(Some(start_of_year)).await.map_or_else(
-            |err| {
-                log::error!("Error fetching top ten yearly: {err:?}");
-                String::from("<tr><td colspan=\"2\">Failed to fetch top ten this year 😩</td></tr>")
-            },
-            |hs| {
-                hs.iter()
-                    .map(HighScoreDto::to_table_row)
-                    .collect::<String>()
-            },
-        );
-
-        dom.query_selector("#topten-yearly tbody")?
-            .ok_or_else(|| Error::new("Cant find topten yearly table"))
-            .map(JsCast::dyn_into::<HtmlElement>)?
-            .map(|table| table.set_inner_html(&top_yearly_html))?;
+        let top_yearly = client.top_ten(Some(start_of_year)).await;
+        set_table(&dom, "#topten-yearly tbody", top_yearly, 1, highlight)?;
     }
 
-    let top_alltime_html = topten_alltime_fut.await.map_or_else(
-        |err| {
-            log::error!("Error fetching top ten alltime: {err:?}");
-            String::from("<tr><td colspan=\"2\">Failed to fetch top ten alltime 😩</td></tr>")
-        },
-        |hs| {
-            hs.iter()
-                .map(HighScoreDto::to_table_row)
-                .collect::<String>()
-        },
-    );
-
-    dom.query_selector("#topten-alltime tbody")?
-        .ok_or_else(|| Error::new("Cant find topten alltime table"))
-        .map(JsCast::dyn_into::<HtmlElement>)?
-        .map(|table| table.set_inner_html(&top_alltime_html))?;
+    set_table(
+        &dom,
+        "#topten-alltime tbody",
+        topten_alltime_fut.await,
+        1,
+        highlight,
+    )?;
 
     Ok(())
 }
 
-pub async fn check_and_submit(client: &HighScoreApi, score: u8) -> Result<(), anyhow::Error> {
-    let top_yearly_scores = client.top_ten(Some(start_of_year()?)).await?;
+/// Where the score would place in this year's top ten, if it made it there at all
+fn yearly_rank(ranks: &[RankDto]) -> Option<u8> {
+    ranks
+        .iter()
+        .find(|rank| rank.period == Period::Year)
+        .and_then(|rank| u8::try_from(rank.rank).ok())
+        .filter(|rank| *rank <= 10)
+}
+
+/// Asks the api where the score would place this year, since it knows all the scores
+pub async fn would_rank(client: &HighScoreApi, score: u8) -> Result<Option<u8>, anyhow::Error> {
+    Ok(yearly_rank(&client.rank(score).await?))
+}
 
-    if top_yearly_scores.len() < 10 || top_yearly_scores.iter().any(|hs| hs.score < score) {
-        log::debug!("Score {score} is a highscore!");
+#[derive(Clone, Debug, PartialEq)]
+pub enum Submission {
+    NotAHighscore,
+    Submitted(HighScoreDto),
+    /// It couldn't be submitted right now, so it will be retried later
+    Queued(HighScoreDto),
+    /// It was a highscore, but the player didn't give a name
+    Declined,
+}
 
-        let highscore = prompt("Please enter your name for the highscore table", None)
-            .map(|user_name| HighScoreDto { user_name, score });
+/// Submits the score if it has a `rank` from `would_rank`, using the name from `ask_name`.
+/// If the rank couldn't be fetched, the score is queued anyway, unless it's 0,
+/// and the api turns it down later if it didn't make the top ten.
+/// `own` is set to the highscore while it's being sent, and kept if it arrived.
+pub async fn check_and_submit<F, Fut>(
+    outbox: &Outbox,
+    score: u8,
+    rank: Result<Option<u8>, anyhow::Error>,
+    player_id: Option<String>,
+    own: &RefCell<Option<HighScoreDto>>,
+    ask_name: F,
+) -> Result<Submission, anyhow::Error>
+where
+    F: FnOnce(&str) -> Fut,
+    Fut: Future<Output = Option<String>>,
+{
+    let rank_known = match rank {
+        Ok(None) => return Ok(Submission::NotAHighscore),
+        Ok(Some(_)) => {
+            log::debug!("Score {score} is a highscore!");
+            true
+        }
+        Err(e) if score == 0 => {
+            log::warn!("Unable to check if score {score} is a highscore due to {e:?}");
+            return Ok(Submission::NotAHighscore);
+        }
+        Err(e) => {
+            log::warn!("Unable to check if score {score} is a highscore due to {e:?}");
+            false
+        }
+    };
 
-        match &highscore {
-            Some(hs) => client.submit(hs).await?,
-            None => log::warn!("highscore submission aborted because no username given"),
-        };
-    }
+    let highscore = ask_name(&name_entry::load_last_name())
+        .await
+        .map(|user_name| HighScoreDto {
+            user_name,
+            score,
+            timestamp: None,
+            player_id,
+        });
 
-    Ok(())
+    match highscore {
+        Some(hs) => {
+            name_entry::save_last_name(&hs.user_name)
+                .unwrap_or_else(|e| log::warn!("Unable to save name due to {e:?}"));
+            if !rank_known {
+                outbox.submit_later(hs.clone());
+                return Ok(Submission::Queued(hs));
+            }
+            // The live update can arrive before the api's response does
+            *own.borrow_mut() = Some(hs.clone());
+            let delivery = outbox.submit(hs.clone()).await;
+            if !matches!(delivery, Ok(Delivery::Sent)) {
+                own.take();
+            }
+            match delivery? {
+                Delivery::Sent => Ok(Submission::Submitted(hs)),
+                Delivery::Queued => Ok(Submission::Queued(hs)),
+            }
+        }
+        None => {
+            log::warn!("highscore submission aborted because no username given");
+            Ok(Submission::Declined)
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use time::format_description::well_known::Rfc3339;
 
     #[test]
-    fn to_table_row_should_include_username() {
+    fn row_should_include_rank_username_score_and_date() {
         let highscore = HighScoreDto {
+            user_name: String::from("<b>testuser</b>"),
+            score: 5,
+            timestamp: Some(OffsetDateTime::parse("2024-03-05T12:00:00Z", &Rfc3339).unwrap()),
+            player_id: None,
+        };
+
+        assert_eq!(
+            ["2", "<b>testuser</b>", "5", "2024-03-05"],
+            row_cells(2, &highscore)
+        );
+    }
+
+    #[test]
+    fn same_highscore_should_match_name_and_score() {
+        let own = HighScoreDto {
             user_name: String::from("testuser"),
-            score: 0,
+            score: 5,
+            timestamp: None,
+            player_id: None,
+        };
+        let other = HighScoreDto {
+            score: 4,
+            ..own.clone()
         };
 
-        assert!(highscore
-            .to_table_row()
-            .contains(&highscore.user_name));
+        assert!(is_same(&own, Some(&own)));
+        assert!(!is_same(&other, Some(&own)));
+        assert!(!is_same(&own, None));
     }
 
     #[test]
-    fn to_table_row_should_include_score() {
-        let highscore = HighScoreDto {
+    fn same_highscore_should_match_timestamps_when_both_have_one() {
+        let at = |time: &str| HighScoreDto {
             user_name: String::from("testuser"),
             score: 5,
+            timestamp: Some(OffsetDateTime::parse(time, &Rfc3339).unwrap()),
+            player_id: None,
+        };
+        let first = at("2024-03-05T12:00:00Z");
+        let second = at("2024-03-06T12:00:00Z");
+        let unsent = HighScoreDto {
+            timestamp: None,
+            ..first.clone()
         };
 
-        assert!(highscore
-            .to_table_row()
-            .contains(&highscore.score.to_string()))
+        assert!(is_same(&first, Some(&first)));
+        assert!(!is_same(&first, Some(&second)));
+        assert!(is_same(&unsent, Some(&second)));
+    }
+
+    fn ranks(today: u64, year: u64) -> Vec<RankDto> {
+        vec![
+            RankDto {
+                period: Period::Today,
+                rank: today,
+            },
+            RankDto {
+                period: Period::Year,
+                rank: year,
+            },
+        ]
+    }
+
+    #[test]
+    fn yearly_rank_should_come_from_the_year_period() {
+        assert_eq!(Some(3), yearly_rank(&ranks(1, 3)));
+        assert_eq!(Some(10), yearly_rank(&ranks(1, 10)));
+    }
+
+    #[test]
+    fn yearly_rank_should_be_none_when_off_the_table() {
+        assert_eq!(None, yearly_rank(&ranks(1, 11)));
+        assert_eq!(None, yearly_rank(&ranks(1, 300)));
+        assert_eq!(None, yearly_rank(&[]));
     }
 }
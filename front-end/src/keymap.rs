@@ -0,0 +1,355 @@
+//! Which keys trigger which actions
+use std::collections::BTreeMap;
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::debug::DEBUG_KEY;
+use crate::storage;
+use crate::vi::ControlScheme;
+
+const STORAGE_KEY: &str = "keymap";
+
+/// Keys which are part of vim motions and counts, or toggle the debug overlay,
+/// and can't be bound to anything else
+const RESERVED_KEYS: [&str; 14] = [
+    "0", "1", "2", "3", "4", "5", "6", "7", "8", "9", "$", "g", "G", DEBUG_KEY,
+];
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Action {
+    Left,
+    Down,
+    Up,
+    Right,
+    Start,
+    Stop,
+    Help,
+    CommandLine,
+    Mute,
+}
+
+impl Action {
+    pub const ALL: [Action; 9] = [
+        Action::Left,
+        Action::Down,
+        Action::Up,
+        Action::Right,
+        Action::Start,
+        Action::Stop,
+        Action::Help,
+        Action::CommandLine,
+        Action::Mute,
+    ];
+
+    /// What the action does with the given control scheme, or `None` if it does nothing
+    fn description(self, scheme: ControlScheme) -> Option<&'static str> {
+        match (scheme, self) {
+            (ControlScheme::Relative, Action::Left) => Some("turn ↺"),
+            (ControlScheme::Relative, Action::Right) => Some("turn ↻"),
+            (ControlScheme::Relative, Action::Up | Action::Down) => None,
+            _ => Some(self.name()),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Action::Left => "left",
+            Action::Down => "down",
+            Action::Up => "up",
+            Action::Right => "right",
+            Action::Start => "start",
+            Action::Stop => "quit",
+            Action::Help => "help",
+            Action::CommandLine => "commands",
+            Action::Mute => "mute",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Action> {
+        Action::ALL.into_iter().find(|a| a.name() == name)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum KeymapError {
+    Conflict {
+        key: String,
+        bound_to: Action,
+    },
+    Reserved(String),
+    /// Removing the key would leave the action without any key
+    LastKey(Action),
+}
+
+impl fmt::Display for KeymapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Conflict { key, bound_to } => write!(
+                f,
+                "{} is already used for {}",
+                display_key(key),
+                bound_to.name()
+            ),
+            Self::Reserved(key) => write!(f, "{} is reserved", display_key(key)),
+            Self::LastKey(action) => write!(f, "{} needs at least one key", action.name()),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Keymap(BTreeMap<Action, Vec<String>>);
+
+impl Default for Keymap {
+    fn default() -> Self {
+        let bindings = [
+            (Action::Left, vec!["h", "ArrowLeft"]),
+            (Action::Down, vec!["j", "ArrowDown"]),
+            (Action::Up, vec!["k", "ArrowUp"]),
+            (Action::Right, vec!["l", "ArrowRight"]),
+            (Action::Start, vec![" "]),
+            (Action::Stop, vec!["q"]),
+            (Action::Help, vec!["?"]),
+            (Action::CommandLine, vec![":"]),
+            (Action::Mute, vec!["m"]),
+        ];
+
+        Keymap(
+            bindings
+                .into_iter()
+                .map(|(action, keys)| (action, keys.into_iter().map(String::from).collect()))
+                .collect(),
+        )
+    }
+}
+
+impl Keymap {
+    /// Loads the keymap saved in local storage, falling back to the default one
+    pub fn load() -> Keymap {
+        let mut keymap = storage::load::<Keymap>(STORAGE_KEY).unwrap_or_default();
+        keymap.add_missing_actions();
+
+        if keymap.has_conflicts() {
+            log::warn!("Ignoring saved keymap because it has conflicting bindings");
+            return Keymap::default();
+        }
+        keymap
+    }
+
+    /// Binds actions added since the keymap was saved to their default keys, if they're free
+    fn add_missing_actions(&mut self) {
+        let defaults = Keymap::default();
+        for action in Action::ALL {
+            if self.keys(action).is_empty() {
+                let free_keys = defaults
+                    .keys(action)
+                    .iter()
+                    .filter(|key| self.action(key).is_none())
+                    .cloned()
+                    .collect();
+                self.0.insert(action, free_keys);
+            }
+        }
+    }
+
+    pub fn save(&self) -> Result<(), wasm_bindgen::JsValue> {
+        storage::save(STORAGE_KEY, self)
+    }
+
+    pub fn action(&self, key: &str) -> Option<Action> {
+        self.0
+            .iter()
+            .find(|(_, keys)| keys.iter().any(|k| k == key))
+            .map(|(action, _)| *action)
+    }
+
+    pub fn keys(&self, action: Action) -> &[String] {
+        self.0.get(&action).map_or(&[], Vec::as_slice)
+    }
+
+    /// The key to mention when telling the user how to do something
+    pub fn first_key(&self, action: Action) -> &str {
+        self.keys(action).first().map_or("", |k| display_key(k))
+    }
+
+    pub fn bind(&mut self, action: Action, key: &str) -> Result<(), KeymapError> {
+        if RESERVED_KEYS.contains(&key) {
+            return Err(KeymapError::Reserved(key.to_string()));
+        }
+        match self.action(key) {
+            Some(bound_to) if bound_to == action => Ok(()),
+            Some(bound_to) => Err(KeymapError::Conflict {
+                key: key.to_string(),
+                bound_to,
+            }),
+            None => {
+                self.0.entry(action).or_default().push(key.to_string());
+                Ok(())
+            }
+        }
+    }
+
+    pub fn unbind(&mut self, action: Action, key: &str) -> Result<(), KeymapError> {
+        let keys = self.0.entry(action).or_default();
+        if keys.len() == 1 && keys[0] == key {
+            return Err(KeymapError::LastKey(action));
+        }
+        keys.retain(|k| k != key);
+        Ok(())
+    }
+
+    fn has_conflicts(&self) -> bool {
+        let mut all_keys = self.0.values().flatten().collect::<Vec<_>>();
+        let count = all_keys.len();
+        all_keys.sort();
+        all_keys.dedup();
+        all_keys.len() != count || all_keys.iter().any(|k| RESERVED_KEYS.contains(&k.as_str()))
+    }
+
+    /// Describes the bindings, one action per line
+    pub fn help_text(&self, scheme: ControlScheme) -> String {
+        let bindings = Action::ALL.into_iter().filter_map(|action| {
+            let keys = self
+                .keys(action)
+                .iter()
+                .map(|k| display_key(k))
+                .collect::<Vec<_>>()
+                .join(" ");
+            Some(format!("{}: {keys}", action.description(scheme)?))
+        });
+
+        bindings
+            .chain([String::from("jump: 0 $ gg G"), String::from("counts: 3l")])
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+/// Shows a key the way it is printed on a keyboard
+pub fn display_key(key: &str) -> &str {
+    match key {
+        " " => "<space>",
+        "ArrowLeft" => "←",
+        "ArrowDown" => "↓",
+        "ArrowUp" => "↑",
+        "ArrowRight" => "→",
+        "Escape" => "<esc>",
+        "Enter" => "<enter>",
+        "Tab" => "<tab>",
+        _ => key,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_keymap_should_use_vim_keys_and_arrows() {
+        let keymap = Keymap::default();
+        assert_eq!(Some(Action::Left), keymap.action("h"));
+        assert_eq!(Some(Action::Left), keymap.action("ArrowLeft"));
+        assert_eq!(Some(Action::Start), keymap.action(" "));
+        assert_eq!(None, keymap.action("x"));
+    }
+
+    #[test]
+    fn bind_should_add_key_to_action() {
+        let mut keymap = Keymap::default();
+        keymap.bind(Action::Left, "a").unwrap();
+        assert_eq!(Some(Action::Left), keymap.action("a"));
+        assert_eq!(Some(Action::Left), keymap.action("h"));
+    }
+
+    #[test]
+    fn bind_should_refuse_key_bound_to_another_action() {
+        let mut keymap = Keymap::default();
+        assert_eq!(
+            Err(KeymapError::Conflict {
+                key: String::from("j"),
+                bound_to: Action::Down
+            }),
+            keymap.bind(Action::Left, "j")
+        );
+        assert_eq!(Some(Action::Down), keymap.action("j"));
+    }
+
+    #[test]
+    fn bind_should_refuse_motion_keys() {
+        let mut keymap = Keymap::default();
+        assert_eq!(
+            Err(KeymapError::Reserved(String::from("G"))),
+            keymap.bind(Action::Down, "G")
+        );
+    }
+
+    #[test]
+    fn bind_should_refuse_the_debug_key() {
+        let mut keymap = Keymap::default();
+        assert_eq!(
+            Err(KeymapError::Reserved(String::from(DEBUG_KEY))),
+            keymap.bind(Action::Help, DEBUG_KEY)
+        );
+        let saved: Keymap = serde_json::from_str(r#"{"Help":["F3"]}"#).unwrap();
+        assert!(saved.has_conflicts());
+    }
+
+    #[test]
+    fn unbind_should_remove_key() {
+        let mut keymap = Keymap::default();
+        keymap.unbind(Action::Left, "h").unwrap();
+        assert_eq!(None, keymap.action("h"));
+    }
+
+    #[test]
+    fn unbind_should_refuse_to_remove_the_last_key() {
+        let mut keymap = Keymap::default();
+        assert_eq!(
+            Err(KeymapError::LastKey(Action::Stop)),
+            keymap.unbind(Action::Stop, "q")
+        );
+    }
+
+    #[test]
+    fn it_detects_conflicts_in_deserialized_keymaps() {
+        let keymap: Keymap = serde_json::from_str(r#"{"Left":["h"],"Right":["h"]}"#).unwrap();
+        assert!(keymap.has_conflicts());
+        assert!(!Keymap::default().has_conflicts());
+    }
+
+    #[test]
+    fn missing_actions_should_only_get_free_default_keys() {
+        let mut keymap: Keymap = serde_json::from_str(r#"{"Left":["h","m"]}"#).unwrap();
+        keymap.add_missing_actions();
+        assert_eq!(Some(Action::Left), keymap.action("m"));
+        assert!(keymap.keys(Action::Mute).is_empty());
+        assert_eq!(Some(Action::Stop), keymap.action("q"));
+        assert!(!keymap.has_conflicts());
+    }
+
+    #[test]
+    fn it_survives_a_roundtrip_through_json() {
+        let mut keymap = Keymap::default();
+        keymap.bind(Action::Help, "F1").unwrap();
+        let json = serde_json::to_string(&keymap).unwrap();
+        assert_eq!(keymap, serde_json::from_str(&json).unwrap());
+    }
+
+    #[test]
+    fn help_text_should_list_the_bound_keys() {
+        let mut keymap = Keymap::default();
+        keymap.bind(Action::Stop, "x").unwrap();
+        let help = keymap.help_text(ControlScheme::Absolute);
+        assert!(help.contains("left: h ←"));
+        assert!(help.contains("start: <space>"));
+        assert!(help.contains("quit: q x"));
+    }
+
+    #[test]
+    fn help_text_should_describe_turns_with_relative_controls() {
+        let help = Keymap::default().help_text(ControlScheme::Relative);
+        assert!(help.contains("turn ↺: h ←"));
+        assert!(help.contains("turn ↻: l →"));
+        assert!(!help.contains("up:"));
+    }
+}
@@ -0,0 +1,215 @@
+//! A leaderboard which can be limited to a period, and paged through beyond the top ten
+use std::cell::Cell;
+use std::rc::Rc;
+
+use gloo_events::EventListener;
+use gloo_utils::document;
+use highscore_types::{HighScoreDto, Period, LEADERBOARD_DEPTH};
+use js_sys::Error;
+use wasm_bindgen::{JsCast, JsValue};
+use wasm_bindgen_futures::spawn_local;
+use web_sys::{Document, HtmlButtonElement, HtmlSelectElement};
+
+use crate::highscores::{self, Highlight};
+use crate::services::highscore_api::HighScoreApi;
+
+const PAGE_SIZE: u32 = 10;
+const TABLE: &str = "#leaderboard-table tbody";
+
+fn label(period: Period) -> &'static str {
+    match period {
+        Period::Today => "Today",
+        Period::Week => "This week",
+        Period::Month => "This month",
+        Period::Year => "This year",
+        Period::All => "All time",
+    }
+}
+
+/// Which part of the leaderboard is shown
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct View {
+    period: Period,
+    /// Counted from 0
+    page: u32,
+}
+
+impl Default for View {
+    fn default() -> Self {
+        Self {
+            period: Period::Week,
+            page: 0,
+        }
+    }
+}
+
+impl View {
+    fn offset(self) -> u32 {
+        self.page * PAGE_SIZE
+    }
+
+    fn previous(self) -> Self {
+        Self {
+            page: self.page.saturating_sub(1),
+            ..self
+        }
+    }
+
+    fn next(self) -> Self {
+        Self {
+            page: self.page + 1,
+            ..self
+        }
+    }
+
+    /// Whether there might be more highscores after the ones shown
+    fn has_next(self, shown: &Result<Vec<HighScoreDto>, anyhow::Error>) -> bool {
+        self.next().offset() < LEADERBOARD_DEPTH
+            && shown
+                .as_ref()
+                .is_ok_and(|highscores| highscores.len() == PAGE_SIZE as usize)
+    }
+}
+
+#[derive(Clone)]
+pub struct Leaderboard {
+    base_url: String,
+    view: Rc<Cell<View>>,
+}
+
+impl Leaderboard {
+    /// Sets up the period selector and paging buttons, and shows the first page
+    pub fn new(doc: &Document, base_url: &str) -> Result<Self, JsValue> {
+        let leaderboard = Self {
+            base_url: base_url.to_string(),
+            view: Rc::new(Cell::new(View::default())),
+        };
+
+        let select: HtmlSelectElement = doc
+            .query_selector("#leaderboard-period")?
+            .map(JsCast::dyn_into)
+            .ok_or_else(|| Error::new("Document had no leaderboard period select"))??;
+        for period in Period::ALL {
+            let option = doc.create_element("option")?;
+            option.set_attribute("value", period.name())?;
+            option.set_text_content(Some(label(period)));
+            select.append_child(&option)?;
+        }
+        select.set_value(View::default().period.name());
+
+        let on_change = {
+            let leaderboard = leaderboard.clone();
+            let select = select.clone();
+            move |_: &web_sys::Event| {
+                let period = Period::from_name(&select.value()).unwrap_or_default();
+                leaderboard.show(View { period, page: 0 }, None);
+            }
+        };
+        EventListener::new(&select, "change", on_change).forget();
+
+        for (id, turn_page) in [
+            ("#leaderboard-previous", View::previous as fn(View) -> View),
+            ("#leaderboard-next", View::next),
+        ] {
+            let button = doc
+                .query_selector(id)?
+                .ok_or_else(|| Error::new(&format!("Document had no {id} button")))?;
+            let leaderboard = leaderboard.clone();
+            let on_click = move |_: &web_sys::Event| {
+                leaderboard.show(turn_page(leaderboard.view.get()), None);
+            };
+            EventListener::new(&button, "click", on_click).forget();
+        }
+
+        leaderboard.refresh(None);
+        Ok(leaderboard)
+    }
+
+    /// Fetches the page shown again, highlighting a highscore if it's there
+    pub fn refresh(&self, highlight: Option<&Highlight>) {
+        self.show(self.view.get(), highlight);
+    }
+
+    fn show(&self, view: View, highlight: Option<&Highlight>) {
+        self.view.set(view);
+        let leaderboard = self.clone();
+        let highlight = highlight.cloned();
+        spawn_local(async move {
+            let api = HighScoreApi::new(&leaderboard.base_url);
+            let highscores = api.leaderboard(view.period, PAGE_SIZE, view.offset()).await;
+            // Another page was picked while this one loaded
+            if leaderboard.view.get() != view {
+                return;
+            }
+            render(&document(), view, highscores, highlight.as_ref())
+                .unwrap_or_else(|e| log::error!("Failed to render leaderboard due to {e:?}"));
+        });
+    }
+}
+
+fn render(
+    doc: &Document,
+    view: View,
+    highscores: Result<Vec<HighScoreDto>, anyhow::Error>,
+    highlight: Option<&Highlight>,
+) -> Result<(), JsValue> {
+    let set_disabled = |id: &str, disabled: bool| -> Result<(), JsValue> {
+        let button: HtmlButtonElement = doc
+            .query_selector(id)?
+            .map(JsCast::dyn_into)
+            .ok_or_else(|| Error::new(&format!("Document had no {id} button")))??;
+        button.set_disabled(disabled);
+        Ok(())
+    };
+    set_disabled("#leaderboard-previous", view.page == 0)?;
+    set_disabled("#leaderboard-next", !view.has_next(&highscores))?;
+
+    if let Some(page) = doc.get_element_by_id("leaderboard-page") {
+        page.set_text_content(Some(&format!("Page {}", view.page + 1)));
+    }
+
+    let first_rank = view.offset() as usize + 1;
+    highscores::set_table(doc, TABLE, highscores, first_rank, highlight)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scores(count: u32) -> Result<Vec<HighScoreDto>, anyhow::Error> {
+        Ok((0..count)
+            .map(|_| HighScoreDto {
+                user_name: String::from("testuser"),
+                score: 5,
+                timestamp: None,
+                player_id: None,
+            })
+            .collect())
+    }
+
+    #[test]
+    fn pages_should_be_offset_by_the_page_size() {
+        let view = View::default().next().next();
+        assert_eq!(20, view.offset());
+        assert_eq!(10, view.previous().offset());
+        assert_eq!(0, View::default().previous().offset());
+    }
+
+    #[test]
+    fn only_full_pages_should_have_a_next() {
+        let view = View::default();
+        assert!(view.has_next(&scores(PAGE_SIZE)));
+        assert!(!view.has_next(&scores(PAGE_SIZE - 1)));
+        assert!(!view.has_next(&Err(anyhow::anyhow!("offline"))));
+    }
+
+    #[test]
+    fn the_last_page_should_not_have_a_next() {
+        let last = View {
+            page: LEADERBOARD_DEPTH / PAGE_SIZE - 1,
+            ..View::default()
+        };
+        assert!(!last.has_next(&scores(PAGE_SIZE)));
+        assert!(last.previous().has_next(&scores(PAGE_SIZE)));
+    }
+}
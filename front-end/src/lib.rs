@@ -1,27 +1,86 @@
 //! Play snake using HTML canvas and web assembly.
 //! Expects a html element with id=phone to exist, and renders the game into that element.
-use futures::stream::StreamExt;
+use futures::channel::{mpsc, oneshot};
+use futures::stream::{self, StreamExt};
 use gloo_dialogs::alert;
-use gloo_timers::callback::Interval;
 use gloo_utils::{document, window};
+use highscore_types::HighScoreDto;
 use js_sys::Error;
+use std::cell::{Cell, RefCell};
 use std::fmt;
+use std::rc::Rc;
 use std::sync::{Arc, RwLock};
 use wasm_bindgen::prelude::*;
 use wasm_bindgen::JsCast;
 use wasm_bindgen_futures::spawn_local;
-use web_sys::HtmlElement;
+use web_sys::{Document, HtmlElement};
 
 mod snake;
 
-use crate::snake::{Direction, Snake};
+use crate::snake::Direction;
 
 mod render;
+mod sprite;
+use crate::render::{Canvas, Renderer};
 
 mod vi;
-use crate::vi::{Command, Vi};
+use crate::vi::Keyboard;
+
+mod touch;
+use crate::touch::Touch;
+
+mod gamepad;
+use crate::gamepad::Gamepads;
+
+mod keymap;
+use crate::keymap::Keymap;
+
+mod storage;
+
+mod settings_screen;
+
+mod cmdline;
+
+mod settings;
+use crate::settings::Settings;
+
+mod theme;
+
+mod a11y;
+
+mod sound;
+use crate::sound::{Sfx, Sound};
+
+mod game;
+use crate::game::{Effect, Game, Input};
+
+mod ghost;
+use crate::ghost::Replay;
+
+mod name_entry;
+use crate::name_entry::NameInput;
+
+mod player;
+
+mod debug;
+use crate::debug::TickTimer;
+
+mod stats;
+
+mod animation;
+use crate::animation::{AnimationFrames, Timestep};
 
 mod highscores;
+use crate::highscores::{Highlight, Submission};
+
+mod leaderboard;
+use crate::leaderboard::Leaderboard;
+
+mod live;
+use crate::live::Live;
+
+mod outbox;
+use crate::outbox::Outbox;
 
 mod services;
 use crate::services::highscore_api::HighScoreApi;
@@ -30,6 +89,7 @@ use crate::services::highscore_api::HighScoreApi;
 pub enum GameStatus {
     NotStarted,
     Playing,
+    Paused,
     GameOver,
 }
 
@@ -38,6 +98,7 @@ impl fmt::Display for GameStatus {
         let disp = match self {
             Self::NotStarted => "Not started",
             Self::Playing => "Playing 🐍",
+            Self::Paused => "Paused ⏸",
             Self::GameOver => "Game over",
         };
         write!(f, "{disp}")
@@ -68,187 +129,317 @@ pub fn run() -> Result<(), JsValue> {
     };
 
     log::debug!("Using highscore api base url {highscore_url}");
+    let doc = document();
+    let outbox = Outbox::new(&highscore_url);
+    outbox.retry_when_online();
     {
         let base_url = highscore_url.clone();
+        let outbox = outbox.clone();
         spawn_local(async move {
+            // Highscores which couldn't be submitted on earlier visits
+            outbox.retry(false).await;
             let highscore_api = HighScoreApi::new(&base_url);
-            highscores::fetch_and_set(&highscore_api)
+            highscores::fetch_and_set(&highscore_api, None)
                 .await
                 .unwrap_or_else(|err| log::error!("Unable to fetch highscores due to {err:?}"));
         });
     }
 
-    let doc = document();
+    let leaderboard = Leaderboard::new(&doc, &highscore_url)
+        .inspect_err(|e| log::warn!("Unable to set up leaderboard due to {e:?}"))
+        .ok();
+    let live = Live::new(&highscore_url, leaderboard);
+    live.connect()
+        .unwrap_or_else(|e| log::warn!("Unable to listen for new highscores due to {e:?}"));
     let html_container: HtmlElement = doc
         .get_element_by_id("phone")
         .ok_or_else(|| Error::new("Could not find a phone element to mount snake into"))
         .map(JsCast::dyn_into)??;
 
+    let keymap = Arc::new(RwLock::new(Keymap::load()));
+    let settings = Arc::new(RwLock::new(Settings::load()));
+    theme::apply_to_page(&doc, settings.read().unwrap().theme)
+        .unwrap_or_else(|e| log::warn!("Unable to apply theme due to {e:?}"));
+    let sound = Sound::new(Arc::clone(&settings));
+    settings_screen::new(
+        &doc,
+        Arc::clone(&keymap),
+        Arc::clone(&settings),
+        sound.clone(),
+    )
+    .unwrap_or_else(|e| log::warn!("Unable to set up settings screen due to {e:?}"));
+    stats::new(&doc).unwrap_or_else(|e| log::warn!("Unable to set up stats due to {e:?}"));
+
     render::new_statusbar(&doc, &html_container)?;
     render::new_canvas(&doc, &html_container)?;
+    {
+        let scheme = settings.read().unwrap().control_scheme;
+        a11y::set_help_text(&doc, &keymap.read().unwrap().help_text(scheme))
+            .unwrap_or_else(|e| log::warn!("Unable to set help text due to {e:?}"));
+    }
 
-    let on_game_over = move |apples| {
-        let base_url = highscore_url.clone();
-        spawn_local(async move {
-            let highscore_api = HighScoreApi::new(&base_url);
-            game_over(&highscore_api, apples)
+    // Results of the end-of-game actions, which the game shows once they arrive
+    let (results, results_receiver) = mpsc::unbounded();
+
+    let on_game_over = {
+        let sound = sound.clone();
+        let settings = Arc::clone(&settings);
+        move |apples| {
+            let base_url = highscore_url.clone();
+            let player_id = settings
+                .read()
+                .unwrap()
+                .share_player_id
+                .then(player::load_or_create_id);
+            let sound = sound.clone();
+            let results = results.clone();
+            let outbox = outbox.clone();
+            let live = live.clone();
+            spawn_local(async move {
+                let highscore_api = HighScoreApi::new(&base_url);
+                let submitted = game_over(
+                    &highscore_api,
+                    &outbox,
+                    apples,
+                    player_id,
+                    &live.own,
+                    &sound,
+                    &results,
+                )
                 .await
                 .unwrap_or_else(|err| {
                     log::error!("End-of-Game actions failed due to {err:?}");
+                    None
                 });
-        });
+                live.refresh_leaderboard(submitted.map(Highlight::Own).as_ref());
+            });
+        }
     };
 
-    game_loop(on_game_over)?;
+    game_loop(keymap, settings, sound, results_receiver, on_game_over)?;
 
     Ok(())
 }
 
-fn game_loop<F: 'static>(on_game_over: F) -> Result<(), JsValue>
+enum LoopEvent {
+    Input(Input),
+    /// Time to draw, at this timestamp in milliseconds
+    AnimationFrame(f64),
+}
+
+fn game_loop<F>(
+    keymap: Arc<RwLock<Keymap>>,
+    settings: Arc<RwLock<Settings>>,
+    sound: Sound,
+    results: mpsc::UnboundedReceiver<Input>,
+    on_game_over: F,
+) -> Result<(), JsValue>
 where
-    F: Fn(u8),
+    F: Fn(u8) + 'static,
 {
-    let snake = Snake::new();
-    let status = Arc::new(RwLock::new(GameStatus::NotStarted));
-    let dir = Arc::new(RwLock::new(snake.direction));
-    let snake = Arc::new(RwLock::new(snake));
-
-    let keylistener = {
-        let snake = Arc::clone(&snake);
-        let status = Arc::clone(&status);
-        let dir = Arc::clone(&dir);
-
-        async move {
-            let doc = document();
-            let mut vi = Vi::new(&doc, Arc::clone(&status));
-
-            while let Some(cmd) = vi.next().await {
-                match cmd {
-                    Command::Start => {
-                        let mut snake = snake.write().unwrap();
-                        let mut game_status = status.write().unwrap();
-                        if *game_status == GameStatus::GameOver {
-                            *snake = Snake::new();
-                            *dir.write().unwrap() = snake.direction;
-                        }
-
-                        *game_status = GameStatus::Playing;
-                        render::update_statusbar(&doc, *game_status).unwrap_or_else(|e| {
-                            log::error!("Failed to update game status due to {e:?}");
-                        });
-
-                        render::clear_canvas(&doc).unwrap_or_else(|e| {
-                            log::error!("Failed to clear screen due to {e:?}");
-                        });
-
-                        render::snake(&doc, &snake)
-                            .unwrap_or_else(|e| log::error!("Failed to draw snake due to {e:?}"));
-                        render::apple(&doc, &snake.target.expect("target was undefined"))
-                            .unwrap_or_else(|e| log::error!("Failed to draw apple due to {e:?}"));
-                    }
-                    Command::Stop => {
-                        let mut snake = snake.write().unwrap();
-                        *snake = snake.kill();
-                    }
-                    Command::Help => {
-                        render::clear_canvas(&doc).unwrap();
-                        render::text(
-                            &doc,
-                            "Navigate: hjkl
-(like in vim),
-arrow keys,
-or phone keys
-
-start: <space>
-quit: <q>",
-                            2,
-                        )
-                        .unwrap_or_else(|e| {
-                            log::error!("Failed to write on canvas due to {e:?}");
-                        });
-                    }
-                    Command::Move(d) => {
-                        let snake = snake.read().unwrap();
-                        if snake.apple_count() == 0 || d != snake.direction.turn_180_degrees() {
-                            *dir.write().unwrap() = d;
-                        }
-                    }
-                }
-            }
-        }
-    };
-    spawn_local(keylistener);
-
-    let apple_counter: HtmlElement = document()
+    let doc = document();
+    let canvas = doc
+        .get_element_by_id("canvas")
+        .ok_or_else(|| Error::new("Document had no canvas"))?;
+    let apple_counter: HtmlElement = doc
         .get_element_by_id("apple-counter")
         .map(JsCast::dyn_into)
         .ok_or_else(|| Error::new("Document had no apple counter"))??;
 
-    let doc = document();
-
-    Interval::new(300, move || {
-        if *status.read().unwrap() != GameStatus::Playing {
-            return;
-        }
-
-        let mut snake_mut = snake.write().unwrap();
-        snake_mut.direction = *dir.read().unwrap();
-        let (moved_snake, old_tail) = snake_mut.move_along();
-        *snake_mut = moved_snake;
-        drop(snake_mut);
+    // Whether the keyboard is typing text, like a name, instead of controlling the game
+    let insert_mode = Rc::new(Cell::new(false));
+    // Lets the keyboard tell whether the game will use a key
+    let status = Rc::new(Cell::new(GameStatus::NotStarted));
+    let commands = stream::select(
+        stream::select(
+            Keyboard::new(
+                &doc,
+                Arc::clone(&keymap),
+                Arc::clone(&settings),
+                Rc::clone(&insert_mode),
+                Rc::clone(&status),
+            ),
+            Touch::new(&canvas, Arc::clone(&settings)),
+        ),
+        stream::select(Gamepads::new(), NameInput::new(&doc)?),
+    )
+    .map(|cmd| LoopEvent::Input(Input::Command(cmd)));
+
+    let mut events = stream::select(
+        stream::select(commands, results.map(LoopEvent::Input)),
+        AnimationFrames::new().map(LoopEvent::AnimationFrame),
+    );
+    let mut timestep = Timestep::default();
+    let mut game = Game::new(Arc::clone(&settings));
+    game.best = Replay::load_personal_best();
+    game.debug = debug::enabled_by_url();
+    let mut tick_timer = TickTimer::default();
+    let mut canvas = Canvas::new(&doc)?;
+
+    spawn_local(async move {
+        // How far the snake has come towards its next step
+        let mut progress = 0.0;
+        // The name last put in the name field, or `None` while it's hidden
+        let mut shown_name: Option<String> = None;
+
+        while let Some(event) = events.next().await {
+            let effects = match event {
+                LoopEvent::Input(input) => game.update(input),
+                LoopEvent::AnimationFrame(now) => {
+                    let tick_millis = f64::from(settings.read().unwrap().tick_millis());
+                    let (ticks, p) = timestep.advance(now, tick_millis);
+                    progress = p;
+                    if game.status != GameStatus::Playing {
+                        tick_timer.reset();
+                    } else if ticks > 0 {
+                        tick_timer.record(now, tick_millis);
+                    }
+                    (0..ticks).flat_map(|_| game.update(Input::Tick)).collect()
+                }
+            };
+            status.set(game.status);
 
-        let snake = snake.read().unwrap();
+            let mut frame = game.frame(&keymap.read().unwrap(), progress);
+            if game.debug {
+                frame.debug = Some(debug::overlay(&game, &tick_timer));
+            }
+            canvas
+                .draw(&frame)
+                .unwrap_or_else(|e| log::error!("Failed to draw frame due to {e:?}"));
 
-        if !snake.alive {
-            *status.write().unwrap() = GameStatus::GameOver;
-            on_game_over(snake.apple_count());
-            return;
-        }
+            for effect in effects {
+                if let Effect::GameOver { apples, .. } = effect {
+                    stats::record(&doc, apples, stats::variant(game.snake.wrap, game.board));
+                }
+                let announce_turns = settings.read().unwrap().announce_turns;
+                if let Some(message) = a11y::describe(&effect, announce_turns) {
+                    a11y::announce(&doc, &message)
+                        .unwrap_or_else(|e| log::error!("Failed to announce due to {e:?}"));
+                }
+                if let Effect::Help = effect {
+                    let scheme = settings.read().unwrap().control_scheme;
+                    a11y::set_help_text(&doc, &keymap.read().unwrap().help_text(scheme))
+                        .unwrap_or_else(|e| log::error!("Failed to set help text due to {e:?}"));
+                }
+                sound
+                    .update(&effect)
+                    .unwrap_or_else(|e| log::error!("Failed to play sound due to {e:?}"));
+                if let Effect::NameEntry(typing) = effect {
+                    insert_mode.set(typing);
+                }
+                if let Effect::Muted(_) = effect {
+                    settings
+                        .read()
+                        .unwrap()
+                        .save_muted()
+                        .unwrap_or_else(|e| log::error!("Failed to save mute due to {e:?}"));
+                }
+                if let Effect::ControlScheme(scheme) = effect {
+                    settings
+                        .read()
+                        .unwrap()
+                        .save_control_scheme()
+                        .unwrap_or_else(|e| {
+                            log::error!("Failed to save control scheme due to {e:?}")
+                        });
+                    settings_screen::show_control_scheme(&doc, scheme).unwrap_or_else(|e| {
+                        log::error!("Failed to show control scheme due to {e:?}")
+                    });
+                }
+                apply(&doc, &apple_counter, &on_game_over, effect);
+            }
 
-        match old_tail {
-            Some(tail) => render::clear_pos(&doc, &tail)
-                .unwrap_or_else(|e| log::error!("Failed to clear tail due to {e:?}")),
-            None if snake.target.is_some() => {
-                render::apple(&doc, &snake.target.expect("target was undefined"))
-                    .unwrap_or_else(|e| log::error!("Failed to draw apple due to {e:?}"));
+            let name = game.entered_name();
+            if name != shown_name.as_deref() {
+                name_entry::show_input(&doc, name)
+                    .unwrap_or_else(|e| log::error!("Failed to show name input due to {e:?}"));
+                shown_name = name.map(String::from);
             }
-            None => render::text(&doc, "💯 u crazy!! 💯", 8)
-                // The snake now covers the whole screen!
-                .unwrap_or_else(|e| log::error!("Failed to write on canvas due to {e:?}")),
         }
-        render::snake(&doc, &snake)
-            .unwrap_or_else(|e| log::error!("Failed to draw snake due to {e:?}"));
-        apple_counter.set_inner_text(&format!("🍎{}", snake.apple_count()));
-    })
-    .forget();
+    });
 
     Ok(())
 }
 
-async fn game_over(highscore_api: &HighScoreApi, apple_count: u8) -> Result<(), JsValue> {
+/// Shows the result of a game update outside of the canvas
+fn apply<F>(doc: &Document, apple_counter: &HtmlElement, on_game_over: &F, effect: Effect)
+where
+    F: Fn(u8),
+{
+    match effect {
+        Effect::Status(status) => render::update_statusbar(doc, status)
+            .unwrap_or_else(|e| log::error!("Failed to update game status due to {e:?}")),
+        Effect::AppleCount(apples) => apple_counter.set_inner_text(&format!("🍎{apples}")),
+        Effect::GameOver { apples, .. } => on_game_over(apples),
+        Effect::NewBest(replay) => replay
+            .save_as_personal_best()
+            .unwrap_or_else(|e| log::error!("Failed to save personal best due to {e:?}")),
+        Effect::NewGame
+        | Effect::AteApple(_)
+        | Effect::Turned(_)
+        | Effect::Help
+        | Effect::Muted(_)
+        | Effect::ControlScheme(_)
+        | Effect::NameEntry(_) => {}
+    }
+}
+
+async fn game_over(
+    highscore_api: &HighScoreApi,
+    outbox: &Outbox,
+    apple_count: u8,
+    player_id: Option<String>,
+    own: &RefCell<Option<HighScoreDto>>,
+    sound: &Sound,
+    results: &mpsc::UnboundedSender<Input>,
+) -> Result<Option<HighScoreDto>, JsValue> {
     let doc = document();
     render::update_statusbar(&doc, GameStatus::GameOver)?;
-    render::text(
-        &doc,
-        &format!(
-            "score: {} {}",
-            apple_count,
-            if apple_count == 1 { "apple" } else { "apples" }
-        ),
-        4,
-    )?;
 
     log::debug!("Checking if score {apple_count} is a highscore");
-    match highscores::check_and_submit(highscore_api, apple_count).await {
-        Ok(()) => {}
+    let ask_name = |prefill: &str| {
+        let (reply, answer) = oneshot::channel();
+        results
+            .unbounded_send(Input::AskName {
+                prefill: String::from(prefill),
+                reply,
+            })
+            .unwrap_or_else(|e| log::error!("Failed to ask for name due to {e:?}"));
+        // Cancelled if the game moved on without an answer
+        async { answer.await.ok().flatten() }
+    };
+    let rank = highscores::would_rank(highscore_api, apple_count).await;
+    if let Ok(Some(rank)) = rank {
+        results
+            .unbounded_send(Input::Rank(rank))
+            .unwrap_or_else(|e| log::error!("Failed to show rank due to {e:?}"));
+    }
+    let submission =
+        highscores::check_and_submit(outbox, apple_count, rank, player_id, own, ask_name).await;
+    let submitted = match submission {
+        Ok(submission) => {
+            if submission != Submission::NotAHighscore {
+                sound
+                    .play(Sfx::Highscore)
+                    .unwrap_or_else(|e| log::error!("Failed to play sound due to {e:?}"));
+            }
+            a11y::announce(&doc, a11y::describe_submission(&submission))
+                .unwrap_or_else(|e| log::error!("Failed to announce due to {e:?}"));
+            match submission {
+                Submission::Submitted(highscore) => Some(highscore),
+                Submission::NotAHighscore | Submission::Queued(_) | Submission::Declined => None,
+            }
+        }
         Err(e) => {
             log::error!("{e:?}");
             alert(&format!("An error occured: {e}"));
+            None
         }
-    }
+    };
 
     log::debug!("Refreshing highscore tables");
-    highscores::fetch_and_set(highscore_api).await?;
+    let highlight = submitted.clone().map(Highlight::Own);
+    highscores::fetch_and_set(highscore_api, highlight.as_ref()).await?;
 
-    Ok(())
+    Ok(submitted)
 }
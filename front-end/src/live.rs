@@ -0,0 +1,176 @@
+//! Updates the highscore tables while the page is open, whenever the api says there's a new
+//! highscore. Listens for server-sent events, and reconnects by itself if the connection is lost.
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+
+use gloo_events::EventListener;
+use gloo_timers::callback::Timeout;
+use highscore_types::HighScoreDto;
+use wasm_bindgen::{JsCast, JsValue};
+use wasm_bindgen_futures::spawn_local;
+use web_sys::{EventSource, MessageEvent};
+
+use crate::highscores::{self, Highlight};
+use crate::leaderboard::Leaderboard;
+use crate::services::highscore_api::HighScoreApi;
+
+/// Wait before reconnecting the first time, doubled for every failed attempt after that
+const FIRST_RECONNECT_MILLIS: u32 = 1_000;
+const MAX_RECONNECT_MILLIS: u32 = 60_000;
+
+/// How long to wait after the given number of failed connection attempts
+fn reconnect_millis(attempts: u32) -> u32 {
+    FIRST_RECONNECT_MILLIS
+        .saturating_mul(2u32.saturating_pow(attempts.saturating_sub(1)))
+        .min(MAX_RECONNECT_MILLIS)
+}
+
+/// The open connection. Dropping it closes the connection and removes its listeners.
+struct Connection {
+    source: EventSource,
+    _listeners: [EventListener; 3],
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        self.source.close();
+    }
+}
+
+#[derive(Clone)]
+pub struct Live {
+    base_url: String,
+    leaderboard: Option<Leaderboard>,
+    /// The highscore the player is submitting, whose tables are refreshed by the game instead
+    pub own: Rc<RefCell<Option<HighScoreDto>>>,
+    connection: Rc<RefCell<Option<Connection>>>,
+    reconnect: Rc<RefCell<Option<Timeout>>>,
+    /// Failed attempts since the connection was last open
+    attempts: Rc<Cell<u32>>,
+    /// Whether the connection has been open before, so highscores might have been missed
+    was_open: Rc<Cell<bool>>,
+}
+
+impl Live {
+    pub fn new(base_url: &str, leaderboard: Option<Leaderboard>) -> Self {
+        Self {
+            base_url: base_url.to_string(),
+            leaderboard,
+            own: Rc::new(RefCell::new(None)),
+            connection: Rc::new(RefCell::new(None)),
+            reconnect: Rc::new(RefCell::new(None)),
+            attempts: Rc::new(Cell::new(0)),
+            was_open: Rc::new(Cell::new(false)),
+        }
+    }
+
+    pub fn connect(&self) -> Result<(), JsValue> {
+        let source = EventSource::new(&format!("{base}/live", base = self.base_url))?;
+
+        let on_highscore = {
+            let live = self.clone();
+            move |event: &web_sys::Event| {
+                let Some(data) = event
+                    .dyn_ref::<MessageEvent>()
+                    .and_then(|e| e.data().as_string())
+                else {
+                    return;
+                };
+                match serde_json::from_str::<HighScoreDto>(&data) {
+                    Ok(highscore) => live.update(Some(highscore)),
+                    Err(e) => log::warn!("Ignoring invalid live highscore due to {e:?}"),
+                }
+            }
+        };
+
+        let on_open = {
+            let live = self.clone();
+            move |_: &web_sys::Event| {
+                log::debug!("Listening for new highscores");
+                live.attempts.set(0);
+                if live.was_open.replace(true) {
+                    // Catch up on whatever was submitted while disconnected
+                    live.update(None);
+                }
+            }
+        };
+
+        let on_error = {
+            let live = self.clone();
+            let source = source.clone();
+            move |_: &web_sys::Event| {
+                // The browser retries by itself, unless it gave up
+                if source.ready_state() == EventSource::CLOSED {
+                    live.schedule_reconnect();
+                }
+            }
+        };
+
+        let listeners = [
+            EventListener::new(&source, "highscore", on_highscore),
+            EventListener::new(&source, "open", on_open),
+            EventListener::new(&source, "error", on_error),
+        ];
+        *self.connection.borrow_mut() = Some(Connection {
+            source,
+            _listeners: listeners,
+        });
+        Ok(())
+    }
+
+    fn schedule_reconnect(&self) {
+        self.attempts.set(self.attempts.get() + 1);
+        let delay = reconnect_millis(self.attempts.get());
+        log::warn!("Lost connection to live highscores, reconnecting in {delay}ms");
+
+        let live = self.clone();
+        *self.reconnect.borrow_mut() = Some(Timeout::new(delay, move || {
+            live.connect().unwrap_or_else(|e| {
+                log::error!("Failed to reconnect to live highscores due to {e:?}");
+                live.schedule_reconnect();
+            });
+        }));
+    }
+
+    pub fn refresh_leaderboard(&self, highlight: Option<&Highlight>) {
+        if let Some(leaderboard) = &self.leaderboard {
+            leaderboard.refresh(highlight);
+        }
+    }
+
+    /// Fetches the tables again, highlighting the new highscore if it made it there
+    fn update(&self, highscore: Option<HighScoreDto>) {
+        if let Some(highscore) = &highscore {
+            // Only the first time, since the same name could get the same score again later
+            let own = self.own.take();
+            if highscores::is_same(highscore, own.as_ref()) {
+                log::debug!("Not refreshing highscores for our own submission");
+                return;
+            }
+            *self.own.borrow_mut() = own;
+        }
+
+        let highlight = highscore.map(Highlight::Entered);
+        self.refresh_leaderboard(highlight.as_ref());
+
+        let base_url = self.base_url.clone();
+        spawn_local(async move {
+            let highscore_api = HighScoreApi::new(&base_url);
+            highscores::fetch_and_set(&highscore_api, highlight.as_ref())
+                .await
+                .unwrap_or_else(|e| log::error!("Unable to update highscores due to {e:?}"));
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reconnecting_should_back_off_until_the_max() {
+        assert_eq!(FIRST_RECONNECT_MILLIS, reconnect_millis(1));
+        assert_eq!(FIRST_RECONNECT_MILLIS * 4, reconnect_millis(3));
+        assert_eq!(MAX_RECONNECT_MILLIS, reconnect_millis(100));
+    }
+}
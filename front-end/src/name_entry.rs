@@ -0,0 +1,290 @@
+//! Typing in a name for the highscore table, on the canvas, like vim's insert mode.
+//! There's a text field for it too, so it can be typed on touch screens.
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures::channel::{mpsc, oneshot};
+use futures::stream::Stream;
+use gloo_events::{EventListener, EventListenerOptions};
+use js_sys::Error;
+use wasm_bindgen::{JsCast, JsValue};
+use web_sys::{Document, HtmlElement, HtmlInputElement, KeyboardEvent};
+
+use crate::storage;
+use crate::vi::Command;
+
+pub const MAX_NAME_LENGTH: usize = 16;
+
+const LAST_NAME_KEY: &str = "last-name";
+
+/// Shown while typing, so players know what they agree to by submitting
+const PRIVACY_NOTICE: &str = "Your name and score\nwill be shown publicly.";
+
+/// A key pressed in insert mode
+#[derive(Clone, Debug, PartialEq)]
+pub enum InsertKey {
+    Char(char),
+    Backspace,
+    Enter,
+    Escape,
+    /// The whole name at once, as typed into the text field
+    Text(String),
+}
+
+#[derive(Debug)]
+pub struct NameEntry {
+    pub name: String,
+    /// Gets the name once it's submitted, or `None` if the player declines.
+    /// Dropping the entry without answering counts as declining.
+    reply: Option<oneshot::Sender<Option<String>>>,
+}
+
+impl NameEntry {
+    pub fn new(prefill: &str, reply: oneshot::Sender<Option<String>>) -> Self {
+        Self {
+            name: prefill.chars().take(MAX_NAME_LENGTH).collect(),
+            reply: Some(reply),
+        }
+    }
+
+    /// Edits the name, and returns whether the entry is done
+    pub fn key(&mut self, key: InsertKey) -> bool {
+        match key {
+            InsertKey::Char(c)
+                if !c.is_control() && self.name.chars().count() < MAX_NAME_LENGTH =>
+            {
+                self.name.push(c);
+                false
+            }
+            InsertKey::Char(_) => false,
+            InsertKey::Backspace => {
+                self.name.pop();
+                false
+            }
+            InsertKey::Text(text) => {
+                self.name = text
+                    .chars()
+                    .filter(|c| !c.is_control())
+                    .take(MAX_NAME_LENGTH)
+                    .collect();
+                false
+            }
+            InsertKey::Enter if self.name.trim().is_empty() => false,
+            InsertKey::Enter => {
+                self.answer(Some(String::from(self.name.trim())));
+                true
+            }
+            InsertKey::Escape => {
+                self.answer(None);
+                true
+            }
+        }
+    }
+
+    fn answer(&mut self, name: Option<String>) {
+        if let Some(reply) = self.reply.take() {
+            // Nobody is waiting for the name any more if this fails
+            let _ = reply.send(name);
+        }
+    }
+
+    pub fn text(&self) -> String {
+        format!(
+            "New highscore!\n\nname: {}_\n\n<enter> to submit\n<esc> to skip\n\n{}",
+            self.name, PRIVACY_NOTICE
+        )
+    }
+}
+
+/// Sends what's typed into the name field, and its submit and skip buttons, as insert mode keys
+pub struct NameInput {
+    pub receiver: mpsc::UnboundedReceiver<Command>,
+    _listeners: [EventListener; 4],
+}
+
+impl NameInput {
+    pub fn new(doc: &Document) -> Result<Self, JsValue> {
+        let (sender, receiver) = mpsc::unbounded();
+        let send = move |key| sender.unbounded_send(Command::Insert(key)).unwrap();
+        let form = form(doc)?;
+        let input = input(doc)?;
+        let skip = doc
+            .get_element_by_id("name-skip")
+            .ok_or_else(|| Error::new("Document had no name skip button"))?;
+
+        let on_input = {
+            let send = send.clone();
+            let input = input.clone();
+            move |_: &web_sys::Event| send(InsertKey::Text(input.value()))
+        };
+
+        let on_keydown = {
+            let send = send.clone();
+            move |event: &web_sys::Event| {
+                let event = event.dyn_ref::<KeyboardEvent>().unwrap();
+                if event.key() == "Escape" {
+                    event.prevent_default();
+                    send(InsertKey::Escape);
+                }
+            }
+        };
+
+        let on_submit = {
+            let send = send.clone();
+            move |event: &web_sys::Event| {
+                event.prevent_default();
+                send(InsertKey::Enter);
+            }
+        };
+
+        let on_skip = move |_: &web_sys::Event| send(InsertKey::Escape);
+
+        Ok(Self {
+            receiver,
+            _listeners: [
+                EventListener::new(&input, "input", on_input),
+                EventListener::new_with_options(
+                    &input,
+                    "keydown",
+                    EventListenerOptions::enable_prevent_default(),
+                    on_keydown,
+                ),
+                EventListener::new_with_options(
+                    &form,
+                    "submit",
+                    EventListenerOptions::enable_prevent_default(),
+                    on_submit,
+                ),
+                EventListener::new(&skip, "click", on_skip),
+            ],
+        })
+    }
+}
+
+impl Stream for NameInput {
+    type Item = Command;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        Pin::new(&mut self.receiver).poll_next(cx)
+    }
+}
+
+fn form(doc: &Document) -> Result<HtmlElement, JsValue> {
+    Ok(doc
+        .get_element_by_id("name-form")
+        .map(JsCast::dyn_into)
+        .ok_or_else(|| Error::new("Document had no name form"))??)
+}
+
+fn input(doc: &Document) -> Result<HtmlInputElement, JsValue> {
+    Ok(doc
+        .get_element_by_id("name-input")
+        .map(JsCast::dyn_into)
+        .ok_or_else(|| Error::new("Document had no name input"))??)
+}
+
+/// Shows the name field with the name being entered, or hides it when there's none
+pub fn show_input(doc: &Document, name: Option<&str>) -> Result<(), JsValue> {
+    let form = form(doc)?;
+    let input = input(doc)?;
+    let Some(name) = name else {
+        form.set_hidden(true);
+        return Ok(());
+    };
+
+    // While it has focus the field is what's being typed into, and setting it would move the caret
+    let focused = doc
+        .active_element()
+        .is_some_and(|element| element == **input);
+    if !focused && input.value() != name {
+        input.set_value(name);
+    }
+    if form.hidden() {
+        form.set_hidden(false);
+        input.focus()?;
+    }
+    Ok(())
+}
+
+/// The name last submitted from this browser, to suggest next time
+pub fn load_last_name() -> String {
+    storage::load(LAST_NAME_KEY).unwrap_or_default()
+}
+
+pub fn save_last_name(name: &str) -> Result<(), JsValue> {
+    storage::save(LAST_NAME_KEY, &name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn type_keys(entry: &mut NameEntry, keys: &str) {
+        for c in keys.chars() {
+            entry.key(InsertKey::Char(c));
+        }
+    }
+
+    #[test]
+    fn enter_should_submit_the_typed_name() {
+        let (reply, mut answer) = oneshot::channel();
+        let mut entry = NameEntry::new("", reply);
+
+        type_keys(&mut entry, "snakey ");
+        assert!(entry.key(InsertKey::Enter));
+
+        assert_eq!(Ok(Some(Some(String::from("snakey")))), answer.try_recv());
+    }
+
+    #[test]
+    fn escape_should_decline() {
+        let (reply, mut answer) = oneshot::channel();
+        let mut entry = NameEntry::new("snakey", reply);
+
+        assert!(entry.key(InsertKey::Escape));
+
+        assert_eq!(Ok(Some(None)), answer.try_recv());
+    }
+
+    #[test]
+    fn enter_should_need_a_name() {
+        let (reply, mut answer) = oneshot::channel();
+        let mut entry = NameEntry::new("", reply);
+
+        type_keys(&mut entry, "  ");
+        assert!(!entry.key(InsertKey::Enter));
+
+        assert_eq!(Ok(None), answer.try_recv());
+    }
+
+    #[test]
+    fn name_should_be_prefilled_editable_and_limited() {
+        let (reply, _answer) = oneshot::channel();
+        let mut entry = NameEntry::new("snakey", reply);
+
+        entry.key(InsertKey::Backspace);
+        type_keys(&mut entry, "_the_snake_of_snakes");
+
+        assert_eq!(MAX_NAME_LENGTH, entry.name.chars().count());
+        assert!(entry.name.starts_with("snake_the"));
+        assert!(entry.text().contains("name: snake_the"));
+    }
+
+    #[test]
+    fn text_should_replace_the_name() {
+        let (reply, _answer) = oneshot::channel();
+        let mut entry = NameEntry::new("snakey", reply);
+
+        assert!(!entry.key(InsertKey::Text(String::from("snake\tthe_snake_of_snakes"))));
+
+        assert_eq!("snakethe_snake_o", entry.name);
+    }
+
+    #[test]
+    fn dropping_the_entry_should_decline() {
+        let (reply, mut answer) = oneshot::channel();
+        drop(NameEntry::new("snakey", reply));
+
+        assert!(answer.try_recv().is_err());
+    }
+}
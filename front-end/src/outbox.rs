@@ -0,0 +1,235 @@
+//! Highscore submissions which couldn't be delivered yet, e.g. because the back-end was asleep.
+//! They're kept in local storage and retried with exponential backoff.
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+
+use gloo_events::EventListener;
+use gloo_timers::callback::Timeout;
+use gloo_utils::window;
+use highscore_types::HighScoreDto;
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use wasm_bindgen_futures::spawn_local;
+
+use crate::services::highscore_api::HighScoreApi;
+use crate::storage;
+
+const OUTBOX_KEY: &str = "pending-submissions";
+
+/// Wait before the first retry, doubled for every failed attempt after that
+const FIRST_RETRY_MILLIS: f64 = 30_000.0;
+const MAX_RETRY_MILLIS: f64 = 6.0 * 60.0 * 60.0 * 1000.0;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+struct Pending {
+    highscore: HighScoreDto,
+    /// Sent with every attempt, so the api only stores the highscore once
+    idempotency_key: String,
+    attempts: u32,
+    /// When to try again, in milliseconds since the epoch
+    retry_at: f64,
+}
+
+impl Pending {
+    fn new(highscore: HighScoreDto) -> Self {
+        Self {
+            highscore,
+            idempotency_key: format!("{:032x}", rand::random::<u128>()),
+            attempts: 0,
+            retry_at: 0.0,
+        }
+    }
+
+    fn failed(&mut self, now: f64) {
+        self.attempts += 1;
+        self.retry_at = now + backoff_millis(self.attempts);
+    }
+}
+
+/// How long to wait after the given number of failed attempts
+fn backoff_millis(attempts: u32) -> f64 {
+    let doublings = attempts.saturating_sub(1).min(32) as i32;
+    (FIRST_RETRY_MILLIS * 2f64.powi(doublings)).min(MAX_RETRY_MILLIS)
+}
+
+/// Whether the api turned the highscore down, so trying again won't help
+fn is_rejected(err: &anyhow::Error) -> bool {
+    err.downcast_ref::<reqwest::Error>()
+        .and_then(reqwest::Error::status)
+        .is_some_and(is_rejection)
+}
+
+/// Other client errors, like timeouts and rate limiting, are worth retrying
+fn is_rejection(status: StatusCode) -> bool {
+    matches!(
+        status,
+        StatusCode::BAD_REQUEST | StatusCode::UNPROCESSABLE_ENTITY
+    )
+}
+
+fn load() -> Vec<Pending> {
+    storage::load(OUTBOX_KEY).unwrap_or_default()
+}
+
+/// Changes the stored submissions. They're reloaded every time,
+/// since another submission might have been queued while waiting for the api.
+fn update(change: impl FnOnce(&mut Vec<Pending>)) -> Vec<Pending> {
+    let mut pending = load();
+    change(&mut pending);
+    storage::save(OUTBOX_KEY, &pending)
+        .unwrap_or_else(|e| log::error!("Failed to save pending submissions due to {e:?}"));
+    pending
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Delivery {
+    Sent,
+    /// Couldn't be sent right now, but will be retried later
+    Queued,
+}
+
+#[derive(Clone)]
+pub struct Outbox {
+    base_url: String,
+    /// Dropping the timeout cancels it, so there's never more than one retry scheduled
+    retry: Rc<RefCell<Option<Timeout>>>,
+    /// Whether a retry is running, since the timer and the online event could both start one
+    retrying: Rc<Cell<bool>>,
+}
+
+impl Outbox {
+    pub fn new(base_url: &str) -> Self {
+        Self {
+            base_url: base_url.to_string(),
+            retry: Rc::new(RefCell::new(None)),
+            retrying: Rc::new(Cell::new(false)),
+        }
+    }
+
+    /// Sends the highscore, or keeps it to send later if the api can't be reached
+    pub async fn submit(&self, highscore: HighScoreDto) -> Result<Delivery, anyhow::Error> {
+        let pending = Pending::new(highscore);
+        let api = HighScoreApi::new(&self.base_url);
+        match api
+            .submit(&pending.highscore, &pending.idempotency_key)
+            .await
+        {
+            Ok(()) => Ok(Delivery::Sent),
+            Err(e) if is_rejected(&e) => Err(e),
+            Err(e) => {
+                log::warn!("Unable to submit highscore, will retry later. Failed due to {e:?}");
+                self.queue(pending);
+                Ok(Delivery::Queued)
+            }
+        }
+    }
+
+    /// Keeps the highscore to send later, without trying now
+    pub fn submit_later(&self, highscore: HighScoreDto) {
+        self.queue(Pending::new(highscore));
+    }
+
+    fn queue(&self, mut pending: Pending) {
+        pending.failed(js_sys::Date::now());
+        let queue = update(|queue| queue.push(pending));
+        self.schedule(&queue);
+    }
+
+    /// Sends the submissions which are due, or all of them if `now_or_never`
+    pub async fn retry(&self, now_or_never: bool) {
+        if self.retrying.replace(true) {
+            log::debug!("Already retrying highscore submissions");
+            return;
+        }
+        let api = HighScoreApi::new(&self.base_url);
+        let due = load()
+            .into_iter()
+            .filter(|pending| now_or_never || pending.retry_at <= js_sys::Date::now());
+
+        for pending in due {
+            let key = pending.idempotency_key.clone();
+            let result = api.submit(&pending.highscore, &key).await;
+            update(|queue| match result {
+                Err(e) if !is_rejected(&e) => {
+                    log::warn!("Retrying highscore submission failed due to {e:?}");
+                    queue
+                        .iter_mut()
+                        .filter(|p| p.idempotency_key == key)
+                        .for_each(|p| p.failed(js_sys::Date::now()));
+                }
+                sent_or_rejected => {
+                    if let Err(e) = sent_or_rejected {
+                        log::error!("Highscore submission was rejected: {e:?}");
+                    }
+                    queue.retain(|p| p.idempotency_key != key);
+                }
+            });
+        }
+
+        self.retrying.set(false);
+        self.schedule(&load());
+    }
+
+    /// Retries straight away whenever the browser comes back online
+    pub fn retry_when_online(&self) {
+        let outbox = self.clone();
+        EventListener::new(&window(), "online", move |_| {
+            let outbox = outbox.clone();
+            spawn_local(async move { outbox.retry(true).await });
+        })
+        .forget();
+    }
+
+    /// Sets a timer for the next submission which is due, if any
+    fn schedule(&self, queue: &[Pending]) {
+        let next = queue.iter().map(|p| p.retry_at).reduce(f64::min);
+        *self.retry.borrow_mut() = next.map(|retry_at| {
+            let delay = (retry_at - js_sys::Date::now()).clamp(0.0, f64::from(u32::MAX));
+            let outbox = self.clone();
+            Timeout::new(delay as u32, move || {
+                spawn_local(async move { outbox.retry(false).await });
+            })
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backoff_should_double_until_the_max() {
+        assert_eq!(FIRST_RETRY_MILLIS, backoff_millis(1));
+        assert_eq!(FIRST_RETRY_MILLIS * 2.0, backoff_millis(2));
+        assert_eq!(FIRST_RETRY_MILLIS * 8.0, backoff_millis(4));
+        assert_eq!(MAX_RETRY_MILLIS, backoff_millis(100));
+    }
+
+    #[test]
+    fn only_invalid_highscores_should_be_rejections() {
+        assert!(is_rejection(StatusCode::BAD_REQUEST));
+        assert!(is_rejection(StatusCode::UNPROCESSABLE_ENTITY));
+        assert!(!is_rejection(StatusCode::REQUEST_TIMEOUT));
+        assert!(!is_rejection(StatusCode::TOO_MANY_REQUESTS));
+        assert!(!is_rejection(StatusCode::INTERNAL_SERVER_ERROR));
+    }
+
+    #[test]
+    fn failed_attempts_should_push_the_retry_back_and_keep_the_key() {
+        let mut pending = Pending::new(HighScoreDto {
+            user_name: String::from("testuser"),
+            score: 5,
+            timestamp: None,
+            player_id: None,
+        });
+        let key = pending.idempotency_key.clone();
+
+        pending.failed(1000.0);
+        pending.failed(2000.0);
+
+        assert_eq!(2, pending.attempts);
+        assert_eq!(2000.0 + FIRST_RETRY_MILLIS * 2.0, pending.retry_at);
+        assert_eq!(key, pending.idempotency_key);
+        assert_eq!(32, key.len());
+    }
+}
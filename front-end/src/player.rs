@@ -0,0 +1,44 @@
+//! An anonymous id for the player, so the api can group their scores without an account
+use crate::storage;
+
+const PLAYER_ID_KEY: &str = "player-id";
+
+/// A random (version 4) uuid
+fn new_id(random: u128) -> String {
+    let version = 0x4 << 76;
+    let variant = 0x2 << 62;
+    let bits = (random & !(0xf << 76) & !(0x3 << 62)) | version | variant;
+    let hex = format!("{bits:032x}");
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
+
+/// This browser's player id, created the first time it's needed
+pub fn load_or_create_id() -> String {
+    storage::load(PLAYER_ID_KEY).unwrap_or_else(|| {
+        let id = new_id(rand::random());
+        storage::save(PLAYER_ID_KEY, &id)
+            .unwrap_or_else(|e| log::warn!("Unable to save player id due to {e:?}"));
+        id
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn id_should_be_a_version_4_uuid() {
+        let id = new_id(u128::MAX);
+        assert_eq!("ffffffff-ffff-4fff-bfff-ffffffffffff", id);
+
+        let id = new_id(0);
+        assert_eq!("00000000-0000-4000-8000-000000000000", id);
+    }
+}
@@ -1,12 +1,21 @@
 //! Utilities for drawing/rendering snake on a fake phone screen
+use std::cell::{Cell, RefCell};
+#[cfg(test)]
+use std::convert::Infallible;
 use std::f64::consts::PI;
+use std::rc::Rc;
 
+use gloo_events::EventListener;
 use js_sys::Error;
 use wasm_bindgen::{JsCast, JsValue};
 use web_sys::{CanvasRenderingContext2d, Document, HtmlCanvasElement, HtmlElement};
 
+use gloo_utils::window;
+
 use crate::{
-    snake::{self, Position, Snake},
+    snake::{self, Direction, Position, Snake},
+    sprite::{self, Segment},
+    theme::Theme,
     GameStatus,
 };
 
@@ -15,6 +24,10 @@ pub fn new_canvas(doc: &Document, parent: &HtmlElement) -> Result<(), JsValue> {
         .create_element("canvas")?
         .dyn_into::<HtmlCanvasElement>()?;
     canvas.set_id("canvas");
+    canvas.set_attribute("role", "img")?;
+    canvas.set_attribute("aria-label", "Snake game board")?;
+    canvas.set_attribute("aria-describedby", "help-text")?;
+    // Until it has been sized to fit the page, see `Canvas::fit`
     canvas.set_width(snake::WIDTH);
     canvas.set_height(snake::HEIGHT);
 
@@ -25,66 +38,543 @@ pub fn new_canvas(doc: &Document, parent: &HtmlElement) -> Result<(), JsValue> {
 
     insert_after.insert_adjacent_element("afterend", &canvas)?;
 
-    text(doc, "Press <space>\nto start\n\nPress <?> for\nhelp", 2)?;
-
     Ok(())
 }
 
-pub fn snake(doc: &Document, snake: &Snake) -> Result<(), JsValue> {
-    let context = get_canvas_context(doc)?;
-    context.set_fill_style_str("#abba00");
-    context.fill_rect(
-        snake.head().x,
-        snake.head().y,
-        snake::LINE_THICKNESS,
-        snake::LINE_THICKNESS,
-    );
-    snake.body.iter().rev().nth(1).inspect(|next| {
-        context.set_fill_style_str("#bada55");
-        context.fill_rect(next.x, next.y, snake::LINE_THICKNESS, snake::LINE_THICKNESS);
-    });
-    Ok(())
+/// Everything which should be on the screen at one point in time
+#[derive(Debug)]
+pub struct Frame<'a> {
+    /// The snake and its apple, if the board is visible
+    pub snake: Option<&'a Snake>,
+    /// Where the snake came from, if it should be drawn part way through its last step
+    pub tween: Option<Tween>,
+    /// How far through its death animation the snake is, from 0 to 1, once it has died
+    pub death: Option<f64>,
+    /// A snake to race against, drawn underneath the real one
+    pub ghost: Option<Layer<'a>>,
+    pub text: Option<Text>,
+    /// Vim-style command line, shown on the bottom row
+    pub cmdline: Option<&'a str>,
+    pub theme: &'static Theme,
+    /// Lines of the developer overlay, if it's on
+    pub debug: Option<Vec<String>>,
 }
 
-pub fn apple(doc: &Document, apple: &Position) -> Result<(), JsValue> {
-    let context = get_canvas_context(doc)?;
-    let radius = (snake::LINE_THICKNESS / 2.0).floor();
-    let x = (apple.x + snake::LINE_THICKNESS / 2.0).round();
-    let y = (apple.y + snake::LINE_THICKNESS / 2.0).round();
+/// A snake drawn faintly, on its own, which nothing on the board can collide with
+#[derive(Debug)]
+pub struct Layer<'a> {
+    pub snake: &'a Snake,
+    pub tween: Option<Tween>,
+}
 
-    context.set_fill_style_str("red");
-    context.begin_path();
-    context.ellipse(x, y, radius, radius, PI / 4.0, 0.0, 2.0 * PI)?;
-    context.fill();
-    context.close_path();
-    Ok(())
+/// Where the ends of the snake were before its last step
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Tween {
+    pub head_from: Position,
+    /// The cell the tail left, unless the snake grew instead
+    pub tail_from: Option<Position>,
+    /// How far through the step to draw the snake, from 0 to 1
+    pub progress: f64,
 }
 
-pub fn clear_pos(doc: &Document, rect: &Position) -> Result<(), JsValue> {
-    let context = get_canvas_context(doc)?;
-    context.clear_rect(rect.x, rect.y, snake::LINE_THICKNESS, snake::LINE_THICKNESS);
-    Ok(())
+impl Tween {
+    /// A point between two neighbouring cells, or just the second cell when
+    /// they aren't neighbours, like when wrapping around the edge
+    fn between(&self, from: &Position, to: &Position) -> Position {
+        let (dx, dy) = (to.x - from.x, to.y - from.y);
+        if dx.abs() + dy.abs() > snake::LINE_THICKNESS {
+            return *to;
+        }
+        Position {
+            x: from.x + dx * self.progress,
+            y: from.y + dy * self.progress,
+        }
+    }
+}
+
+/// Lines of text, starting at the given row of the board
+#[derive(Debug, PartialEq)]
+pub struct Text {
+    pub content: String,
+    pub row: u8,
+}
+
+/// Draws whole frames, replacing whatever was drawn before
+pub trait Renderer {
+    type Error;
+
+    fn draw(&mut self, frame: &Frame) -> Result<(), Self::Error>;
 }
 
-pub fn clear_canvas(doc: &Document) -> Result<(), JsValue> {
-    let context = get_canvas_context(doc)?;
-    context.clear_rect(0.0, 0.0, f64::from(snake::WIDTH), f64::from(snake::HEIGHT));
+/// Font size for text on the board, relative to the size of a cell
+const TEXT_SIZE: f64 = 1.2;
+const CMDLINE_TEXT_SIZE: f64 = 0.64;
+/// Space between text and the left edge of the board
+const TEXT_MARGIN: f64 = 10.0;
+/// Radius of the snake's rounded corners, relative to the size of a cell
+const CORNER_RADIUS: f64 = 0.4;
+/// Width of the tip of the tail, relative to the size of a cell
+const TAIL_TIP: f64 = 0.3;
+/// How many times the snake blinks when it dies
+const BLINKS: f64 = 3.0;
+/// Opacity of the ghost snake
+const GHOST_ALPHA: f64 = 0.35;
+const DEBUG_TEXT_SIZE: f64 = 0.48;
+
+/// Renders onto the game's canvas element.
+///
+/// Everything is drawn in board coordinates, from 0 to `WIDTH` and `HEIGHT`,
+/// which are scaled to however many device pixels the canvas covers.
+pub struct Canvas {
+    element: HtmlCanvasElement,
+    context: CanvasRenderingContext2d,
+    /// Device pixels per board unit
+    scale: f64,
+    /// Whether the backing store might not match the displayed size any more
+    resized: Rc<Cell<bool>>,
+    _on_resize: EventListener,
+    _on_pixel_ratio_change: Rc<RefCell<Option<EventListener>>>,
+}
+
+/// Size of the canvas' backing store, in device pixels, and the scale from board units to it
+fn backing_size(css_width: f64, device_pixel_ratio: f64) -> (u32, f64) {
+    let css_width = if css_width > 0.0 {
+        css_width
+    } else {
+        f64::from(snake::WIDTH)
+    };
+    let pixels = (css_width * device_pixel_ratio.max(1.0)).round();
+    (pixels as u32, pixels / f64::from(snake::WIDTH))
+}
+
+/// Flags `resized` when the pixel density changes, e.g. when zooming or moving the window
+/// to another screen. The media query only matches the current density, so it's replaced
+/// after every change.
+fn watch_pixel_ratio(
+    resized: Rc<Cell<bool>>,
+    listener: Rc<RefCell<Option<EventListener>>>,
+) -> Result<(), JsValue> {
+    let query = format!("(resolution: {}dppx)", window().device_pixel_ratio());
+    let media = window()
+        .match_media(&query)?
+        .ok_or_else(|| Error::new("Unable to watch the pixel density"))?;
+    *listener.borrow_mut() = Some(EventListener::new(&media, "change", {
+        let listener = Rc::clone(&listener);
+        move |_| {
+            resized.set(true);
+            watch_pixel_ratio(Rc::clone(&resized), Rc::clone(&listener))
+                .unwrap_or_else(|e| log::error!("Failed to watch pixel density due to {e:?}"));
+        }
+    }));
     Ok(())
 }
 
-pub fn text(doc: &Document, txt: &str, row: u8) -> Result<(), JsValue> {
-    if txt.contains('\n') {
-        txt.lines()
-            .enumerate()
-            .try_for_each(|(i, l)| text(doc, l, row + i as u8))?;
-        return Ok(());
+/// Font size which fits the longest line within the board, but is no larger than `max`.
+/// The width of the line is in board units, when drawn at size `max`.
+fn fitting_font_size(max: f64, widest_line_at_max: f64) -> f64 {
+    let available = f64::from(snake::WIDTH) - 2.0 * TEXT_MARGIN;
+    if widest_line_at_max > available {
+        max * available / widest_line_at_max
+    } else {
+        max
+    }
+}
+
+impl Canvas {
+    pub fn new(doc: &Document) -> Result<Self, JsValue> {
+        let canvas: HtmlCanvasElement = doc
+            .get_element_by_id("canvas")
+            .map(JsCast::dyn_into)
+            .ok_or_else(|| Error::new("Document had no canvas"))??;
+
+        let context = canvas
+            .get_context("2d")?
+            .ok_or_else(|| Error::new("Canvas had no 2d context"))?
+            .dyn_into::<CanvasRenderingContext2d>()?;
+
+        let resized = Rc::new(Cell::new(false));
+        let on_resize = {
+            let resized = Rc::clone(&resized);
+            EventListener::new(&window(), "resize", move |_| resized.set(true))
+        };
+        let on_pixel_ratio_change = Rc::new(RefCell::new(None));
+        watch_pixel_ratio(Rc::clone(&resized), Rc::clone(&on_pixel_ratio_change))?;
+
+        let mut canvas = Self {
+            element: canvas,
+            context,
+            scale: 1.0,
+            resized,
+            _on_resize: on_resize,
+            _on_pixel_ratio_change: on_pixel_ratio_change,
+        };
+        canvas.fit()?;
+        Ok(canvas)
     }
 
-    let context = get_canvas_context(doc)?;
-    context.set_font("30px monospace");
-    context.set_fill_style_str("blue");
-    context.fill_text(txt, 10.0, f64::from(row) * snake::LINE_THICKNESS)?;
-    Ok(())
+    /// Matches the backing store to the displayed size and pixel density.
+    /// Reading the size forces a layout, so it's only done once something changed.
+    fn fit(&mut self) -> Result<(), JsValue> {
+        let css_width = self.element.get_bounding_client_rect().width();
+        let (pixels, scale) = backing_size(css_width, window().device_pixel_ratio());
+        if pixels == self.element.width() && pixels == self.element.height() {
+            return Ok(());
+        }
+
+        // Resizing resets the context, including its transform
+        self.element.set_width(pixels);
+        self.element.set_height(pixels);
+        self.context
+            .set_transform(scale, 0.0, 0.0, scale, 0.0, 0.0)?;
+        self.scale = scale;
+        Ok(())
+    }
+
+    fn font(&self, size: f64) {
+        self.context
+            .set_font(&format!("{}px monospace", size * snake::LINE_THICKNESS));
+    }
+
+    fn grid(&self, color: &str) {
+        self.context.set_stroke_style_str(color);
+        // One device pixel wide, however large the board is
+        self.context.set_line_width(1.0 / self.scale);
+        self.context.begin_path();
+        for col in 1..snake::COLUMNS {
+            let x = f64::from(col) * snake::LINE_THICKNESS;
+            self.context.move_to(x, 0.0);
+            self.context.line_to(x, f64::from(snake::HEIGHT));
+        }
+        for row in 1..snake::ROWS {
+            let y = f64::from(row) * snake::LINE_THICKNESS;
+            self.context.move_to(0.0, y);
+            self.context.line_to(f64::from(snake::WIDTH), y);
+        }
+        self.context.stroke();
+    }
+
+    fn snake(
+        &self,
+        snake: &Snake,
+        tween: Option<&Tween>,
+        death: Option<f64>,
+        theme: &Theme,
+    ) -> Result<(), JsValue> {
+        if let Some(death) = death {
+            // Blink a few times, then stay faded out
+            if death < 1.0 && (death * BLINKS * 2.0) as u32 % 2 == 1 {
+                return Ok(());
+            }
+            self.context.set_global_alpha(1.0 - 0.6 * death);
+        }
+
+        self.context.set_fill_style_str(theme.body);
+        for segment in sprite::segments(snake) {
+            match segment {
+                Segment::Tail { pos, towards_head } => {
+                    match tween.and_then(|t| Some((t, t.tail_from?))) {
+                        // The tail slides out of the cell it's leaving, so until it's gone
+                        // its current cell is still part of the body
+                        Some((tween, tail_from)) => {
+                            let behind = pos
+                                .direction_to(&tail_from)
+                                .unwrap_or(towards_head.turn_180_degrees());
+                            self.rounded_cell(&pos, &[behind, towards_head])?;
+                            self.tail(&tween.between(&tail_from, &pos), behind.turn_180_degrees());
+                        }
+                        None => self.tail(&pos, towards_head),
+                    }
+                }
+                Segment::Body { pos, .. } => self.rounded_cell(&pos, &segment.connections())?,
+                // ...while the head slides into the cell it's entering
+                Segment::Head { pos, facing, .. } => {
+                    let pos = tween.map_or(pos, |t| t.between(&t.head_from, &pos));
+                    self.context.set_fill_style_str(theme.head);
+                    self.rounded_cell(&pos, &segment.connections())?;
+                    self.eyes(&pos, facing, snake.alive, theme)?;
+                }
+            }
+        }
+
+        self.context.set_global_alpha(1.0);
+        Ok(())
+    }
+
+    /// Fills a cell, rounding the corners which don't touch the connected sides
+    fn rounded_cell(&self, pos: &Position, connected: &[Direction]) -> Result<(), JsValue> {
+        use Direction::*;
+        let l = snake::LINE_THICKNESS;
+        let (x, y) = (pos.x, pos.y);
+        // Each corner, clockwise from the top right, followed by the middle of the next side
+        let corners = [
+            ((x + l, y), (x + l, y + l / 2.0), [Up, Right]),
+            ((x + l, y + l), (x + l / 2.0, y + l), [Down, Right]),
+            ((x, y + l), (x, y + l / 2.0), [Down, Left]),
+            ((x, y), (x + l / 2.0, y), [Up, Left]),
+        ];
+
+        self.context.begin_path();
+        self.context.move_to(x + l / 2.0, y);
+        for ((cx, cy), (nx, ny), sides) in corners {
+            let radius = if sides.iter().any(|side| connected.contains(side)) {
+                0.0
+            } else {
+                CORNER_RADIUS * l
+            };
+            self.context.arc_to(cx, cy, nx, ny, radius)?;
+        }
+        self.context.close_path();
+        self.context.fill();
+        Ok(())
+    }
+
+    /// A wedge, full width where it joins the body and narrowing to the tip
+    fn tail(&self, pos: &Position, towards_head: Direction) {
+        let l = snake::LINE_THICKNESS;
+        let (hx, hy) = sprite::vector(towards_head);
+        let (px, py) = (-hy, hx);
+        let (cx, cy) = (pos.x + l / 2.0, pos.y + l / 2.0);
+        let corner = |along: f64, across: f64| {
+            (
+                cx + (hx * along + px * across) * l,
+                cy + (hy * along + py * across) * l,
+            )
+        };
+
+        let points = [
+            corner(0.5, 0.5),
+            corner(0.5, -0.5),
+            corner(-0.5, -TAIL_TIP / 2.0),
+            corner(-0.5, TAIL_TIP / 2.0),
+        ];
+        self.context.begin_path();
+        self.context.move_to(points[0].0, points[0].1);
+        for (x, y) in &points[1..] {
+            self.context.line_to(*x, *y);
+        }
+        self.context.close_path();
+        self.context.fill();
+    }
+
+    /// Two eyes looking ahead, or crossed out once the snake is dead
+    fn eyes(
+        &self,
+        head: &Position,
+        facing: Direction,
+        alive: bool,
+        theme: &Theme,
+    ) -> Result<(), JsValue> {
+        let l = snake::LINE_THICKNESS;
+        let (fx, fy) = sprite::vector(facing);
+        let (px, py) = (-fy, fx);
+
+        for side in [-1.0, 1.0] {
+            let x = head.x + l / 2.0 + (fx * 0.15 + px * side * 0.22) * l;
+            let y = head.y + l / 2.0 + (fy * 0.15 + py * side * 0.22) * l;
+            if alive {
+                self.context.set_fill_style_str(theme.background);
+                self.context.begin_path();
+                self.context.arc(x, y, 0.12 * l, 0.0, 2.0 * PI)?;
+                self.context.fill();
+
+                self.context.set_fill_style_str(theme.text);
+                self.context.begin_path();
+                self.context.arc(
+                    x + fx * 0.04 * l,
+                    y + fy * 0.04 * l,
+                    0.06 * l,
+                    0.0,
+                    2.0 * PI,
+                )?;
+                self.context.fill();
+            } else {
+                let r = 0.1 * l;
+                self.context.set_stroke_style_str(theme.background);
+                self.context.set_line_width(0.06 * l);
+                self.context.begin_path();
+                self.context.move_to(x - r, y - r);
+                self.context.line_to(x + r, y + r);
+                self.context.move_to(x + r, y - r);
+                self.context.line_to(x - r, y + r);
+                self.context.stroke();
+            }
+        }
+        Ok(())
+    }
+
+    fn apple(&self, apple: &Position, theme: &Theme) -> Result<(), JsValue> {
+        let radius = (snake::LINE_THICKNESS / 2.0).floor();
+        let x = (apple.x + snake::LINE_THICKNESS / 2.0).round();
+        let y = (apple.y + snake::LINE_THICKNESS / 2.0).round();
+
+        self.context.set_fill_style_str(theme.apple);
+        self.context.begin_path();
+        self.context
+            .ellipse(x, y, radius, radius, PI / 4.0, 0.0, 2.0 * PI)?;
+        self.context.fill();
+        self.context.close_path();
+        Ok(())
+    }
+
+    fn text(&self, text: &Text, theme: &Theme) -> Result<(), JsValue> {
+        self.font(TEXT_SIZE);
+        let widest = text
+            .content
+            .lines()
+            .map(|line| self.context.measure_text(line).map(|m| m.width()))
+            .try_fold(0.0, |widest: f64, width| {
+                Ok::<_, JsValue>(widest.max(width?))
+            })?;
+        self.font(fitting_font_size(TEXT_SIZE, widest));
+
+        self.context.set_fill_style_str(theme.text);
+        for (i, line) in text.content.lines().enumerate() {
+            let row = f64::from(text.row) + i as f64;
+            self.context
+                .fill_text(line, TEXT_MARGIN, row * snake::LINE_THICKNESS)?;
+        }
+        Ok(())
+    }
+
+    fn debug(&self, lines: &[String], theme: &Theme) -> Result<(), JsValue> {
+        let line_height = DEBUG_TEXT_SIZE * snake::LINE_THICKNESS;
+        self.context.set_global_alpha(0.8);
+        self.context.set_fill_style_str(theme.background);
+        self.context.fill_rect(
+            0.0,
+            0.0,
+            f64::from(snake::WIDTH),
+            line_height * (lines.len() as f64 + 0.5),
+        );
+        self.context.set_global_alpha(1.0);
+
+        self.font(DEBUG_TEXT_SIZE);
+        self.context.set_fill_style_str(theme.text);
+        for (i, line) in lines.iter().enumerate() {
+            self.context
+                .fill_text(line, 2.0, line_height * (i as f64 + 1.0))?;
+        }
+        Ok(())
+    }
+
+    fn cmdline(&self, txt: &str, theme: &Theme) -> Result<(), JsValue> {
+        self.context.set_fill_style_str(theme.background);
+        self.context.fill_rect(
+            0.0,
+            f64::from(snake::HEIGHT) - snake::LINE_THICKNESS,
+            f64::from(snake::WIDTH),
+            snake::LINE_THICKNESS,
+        );
+        self.font(CMDLINE_TEXT_SIZE);
+        self.context.set_fill_style_str(theme.text);
+        self.context
+            .fill_text(txt, 2.0, f64::from(snake::HEIGHT) - 7.0)
+    }
+}
+
+impl Renderer for Canvas {
+    type Error = JsValue;
+
+    fn draw(&mut self, frame: &Frame) -> Result<(), JsValue> {
+        if self.resized.replace(false) {
+            self.fit()?;
+        }
+        let theme = frame.theme;
+        self.context.set_fill_style_str(theme.background);
+        self.context
+            .fill_rect(0.0, 0.0, f64::from(snake::WIDTH), f64::from(snake::HEIGHT));
+
+        if let Some(snake) = frame.snake {
+            // The debug overlay always shows the grid, to read off cells
+            if let Some(color) = theme.grid.or(frame.debug.as_ref().map(|_| theme.text)) {
+                self.grid(color);
+            }
+            if let Some(ghost) = &frame.ghost {
+                self.context.set_global_alpha(GHOST_ALPHA);
+                self.snake(ghost.snake, ghost.tween.as_ref(), None, theme)?;
+                self.context.set_global_alpha(1.0);
+            }
+            self.snake(snake, frame.tween.as_ref(), frame.death, theme)?;
+            if let Some(target) = &snake.target {
+                self.apple(target, theme)?;
+            }
+        }
+        if let Some(text) = &frame.text {
+            self.text(text, theme)?;
+        }
+        if let Some(cmdline) = frame.cmdline {
+            self.cmdline(cmdline, theme)?;
+        }
+        if let Some(lines) = &frame.debug {
+            self.debug(lines, theme)?;
+        }
+        Ok(())
+    }
+}
+
+/// Renders as plain text, one character per cell, for tests
+#[cfg(test)]
+#[derive(Debug, Default)]
+pub struct Ascii {
+    pub screen: String,
+}
+
+#[cfg(test)]
+impl Ascii {
+    const EMPTY: char = '.';
+    const BODY: char = 'o';
+    const HEAD: char = '@';
+    const APPLE: char = '*';
+    const GHOST: char = 'g';
+}
+
+#[cfg(test)]
+impl Renderer for Ascii {
+    type Error = Infallible;
+
+    fn draw(&mut self, frame: &Frame) -> Result<(), Infallible> {
+        let mut rows = vec![vec![Self::EMPTY; snake::COLUMNS as usize]; snake::ROWS as usize];
+        let mut set = |pos: &Position, c| {
+            if let Some(cell) = rows
+                .get_mut(pos.row() as usize)
+                .and_then(|row| row.get_mut(pos.column() as usize))
+            {
+                *cell = c;
+            }
+        };
+
+        if let Some(ghost) = &frame.ghost {
+            for pos in &ghost.snake.body {
+                set(pos, Self::GHOST);
+            }
+        }
+        if let Some(snake) = frame.snake {
+            if let Some(target) = &snake.target {
+                set(target, Self::APPLE);
+            }
+            for pos in &snake.body {
+                set(pos, Self::BODY);
+            }
+            set(snake.head(), Self::HEAD);
+        }
+
+        let mut lines: Vec<String> = rows.into_iter().map(String::from_iter).collect();
+        // Text on the canvas sits on top of its row, so it covers the row above
+        if let Some(text) = &frame.text {
+            for (i, line) in text.content.lines().enumerate() {
+                let row = (usize::from(text.row) + i).saturating_sub(1);
+                if let Some(l) = lines.get_mut(row) {
+                    *l = String::from(line);
+                }
+            }
+        }
+        if let (Some(cmdline), Some(last)) = (frame.cmdline, lines.last_mut()) {
+            *last = String::from(cmdline);
+        }
+
+        self.screen = lines.join("\n");
+        Ok(())
+    }
 }
 
 pub fn new_statusbar(doc: &Document, parent: &HtmlElement) -> Result<(), JsValue> {
@@ -115,16 +605,157 @@ pub fn update_statusbar(doc: &Document, status: GameStatus) -> Result<(), JsValu
     Ok(())
 }
 
-fn get_canvas_context(doc: &Document) -> Result<CanvasRenderingContext2d, JsValue> {
-    let canvas = doc
-        .get_element_by_id("canvas")
-        .expect("no canvas element could be found")
-        .dyn_into::<HtmlCanvasElement>()?;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::theme::ThemeName;
 
-    let context = canvas
-        .get_context("2d")?
-        .unwrap()
-        .dyn_into::<CanvasRenderingContext2d>()?;
+    fn draw(frame: &Frame) -> Vec<String> {
+        let mut ascii = Ascii::default();
+        ascii.draw(frame).unwrap();
+        ascii.screen.lines().map(String::from).collect()
+    }
+
+    #[test]
+    fn ascii_should_draw_snake_and_apple() {
+        let mut snake = Snake::with_seed(1);
+        snake.target = Some(Position { x: 50.0, y: 25.0 });
+
+        let screen = draw(&Frame {
+            snake: Some(&snake),
+            tween: None,
+            death: None,
+            ghost: None,
+            debug: None,
+            text: None,
+            cmdline: None,
+            theme: ThemeName::default().theme(),
+        });
+
+        assert_eq!(snake::ROWS as usize, screen.len());
+        assert_eq!("@...........", screen[0]);
+        assert_eq!("..*.........", screen[1]);
+    }
+
+    #[test]
+    fn ascii_should_draw_ghost_underneath_the_snake() {
+        let mut snake = Snake::with_seed(1);
+        snake.target = None;
+        let mut ghost = Snake::with_seed(1);
+        ghost.body = vec![Position { x: 0.0, y: 0.0 }, Position { x: 25.0, y: 0.0 }];
+
+        let screen = draw(&Frame {
+            snake: Some(&snake),
+            tween: None,
+            death: None,
+            ghost: Some(Layer {
+                snake: &ghost,
+                tween: None,
+            }),
+            debug: None,
+            text: None,
+            cmdline: None,
+            theme: ThemeName::default().theme(),
+        });
+
+        assert!(screen[0].starts_with("@g"));
+    }
+
+    #[test]
+    fn ascii_should_draw_text_and_cmdline_over_the_board() {
+        let screen = draw(&Frame {
+            snake: None,
+            tween: None,
+            death: None,
+            ghost: None,
+            debug: None,
+            text: Some(Text {
+                content: String::from("hello\nworld"),
+                row: 2,
+            }),
+            cmdline: Some(":set wrap"),
+            theme: ThemeName::default().theme(),
+        });
+
+        assert_eq!("............", screen[0]);
+        assert_eq!("hello", screen[1]);
+        assert_eq!("world", screen[2]);
+        assert_eq!(":set wrap", screen[snake::ROWS as usize - 1]);
+    }
 
-    Ok(context)
+    #[test]
+    fn ascii_should_replace_the_previous_frame() {
+        let mut ascii = Ascii::default();
+        let snake = Snake::with_seed(1);
+        ascii
+            .draw(&Frame {
+                snake: Some(&snake),
+                tween: None,
+                death: None,
+                ghost: None,
+                debug: None,
+                text: None,
+                cmdline: None,
+                theme: ThemeName::default().theme(),
+            })
+            .unwrap();
+        ascii
+            .draw(&Frame {
+                snake: None,
+                tween: None,
+                death: None,
+                ghost: None,
+                debug: None,
+                text: None,
+                cmdline: None,
+                theme: ThemeName::default().theme(),
+            })
+            .unwrap();
+
+        assert!(!ascii.screen.contains('@'));
+    }
+
+    #[test]
+    fn tween_should_move_between_neighbouring_cells() {
+        let tween = Tween {
+            head_from: Position { x: 0.0, y: 0.0 },
+            tail_from: None,
+            progress: 0.4,
+        };
+        assert_eq!(
+            Position { x: 10.0, y: 0.0 },
+            tween.between(&Position { x: 0.0, y: 0.0 }, &Position { x: 25.0, y: 0.0 })
+        );
+    }
+
+    #[test]
+    fn tween_should_jump_when_wrapping_around() {
+        let tween = Tween {
+            head_from: Position { x: 275.0, y: 0.0 },
+            tail_from: None,
+            progress: 0.4,
+        };
+        assert_eq!(
+            Position { x: 0.0, y: 0.0 },
+            tween.between(&Position { x: 275.0, y: 0.0 }, &Position { x: 0.0, y: 0.0 })
+        );
+    }
+
+    #[test]
+    fn backing_store_should_cover_every_device_pixel() {
+        assert_eq!((300, 1.0), backing_size(300.0, 1.0));
+        assert_eq!((600, 2.0), backing_size(300.0, 2.0));
+        assert_eq!((900, 3.0), backing_size(450.0, 2.0));
+    }
+
+    #[test]
+    fn hidden_canvas_should_keep_the_board_size() {
+        assert_eq!((300, 1.0), backing_size(0.0, 1.0));
+    }
+
+    #[test]
+    fn long_lines_should_shrink_the_font() {
+        assert_eq!(1.2, fitting_font_size(1.2, 200.0));
+        assert_eq!(0.6, fitting_font_size(1.2, 560.0));
+    }
 }
@@ -1,4 +1,4 @@
-use highscore_types::HighScoreDto;
+use highscore_types::{HighScoreDto, Period, RankDto};
 use reqwest::Client;
 use serde::Serialize;
 use time::OffsetDateTime;
@@ -14,6 +14,13 @@ struct QueryParams {
     since: OffsetDateTime,
 }
 
+#[derive(Serialize)]
+struct LeaderboardParams {
+    period: Period,
+    limit: u32,
+    offset: u32,
+}
+
 impl HighScoreApi {
     pub fn new(base_url: &str) -> Self {
         Self {
@@ -44,9 +51,55 @@ impl HighScoreApi {
         Ok(res)
     }
 
-    pub async fn submit(&self, highscore: &HighScoreDto) -> Result<(), anyhow::Error> {
+    /// Up to `limit` highscores from the period, skipping the `offset` best ones
+    pub async fn leaderboard(
+        &self,
+        period: Period,
+        limit: u32,
+        offset: u32,
+    ) -> Result<Vec<HighScoreDto>, anyhow::Error> {
+        let query_params = serde_qs::to_string(&LeaderboardParams {
+            period,
+            limit,
+            offset,
+        })?;
+        let request_url = format!("{base}/leaderboard?{query_params}", base = self.base_url);
+
+        let res = self
+            .client
+            .get(&request_url)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        Ok(res)
+    }
+
+    /// Where the score would place in each period's leaderboard
+    pub async fn rank(&self, score: u8) -> Result<Vec<RankDto>, anyhow::Error> {
+        let res = self
+            .client
+            .get(format!("{base}/rank?score={score}", base = self.base_url))
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        Ok(res)
+    }
+
+    /// Submits the highscore. Retries must use the same `idempotency_key`,
+    /// so the api can tell them apart from new highscores.
+    /// The api's cors settings must allow the `Idempotency-Key` header, see terraform/api.tf.
+    pub async fn submit(
+        &self,
+        highscore: &HighScoreDto,
+        idempotency_key: &str,
+    ) -> Result<(), anyhow::Error> {
         self.client
             .post(format!("{base}/submit", base = self.base_url))
+            .header("Idempotency-Key", idempotency_key)
             .json(highscore)
             .send()
             .await?
@@ -67,4 +120,15 @@ mod tests {
         let query_params = serde_qs::to_string(&QueryParams { since }).unwrap();
         assert_eq!("since=2021-01-01T00%3A00%3A00Z", &query_params);
     }
+
+    #[test]
+    fn should_serialize_leaderboard_params_correctly() {
+        let query_params = serde_qs::to_string(&LeaderboardParams {
+            period: Period::Week,
+            limit: 10,
+            offset: 20,
+        })
+        .unwrap();
+        assert_eq!("period=week&limit=10&offset=20", &query_params);
+    }
 }
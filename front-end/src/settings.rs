@@ -0,0 +1,142 @@
+//! Game settings which can be changed while playing, e.g. through the command line
+use wasm_bindgen::JsValue;
+
+use crate::storage;
+use crate::theme::ThemeName;
+use crate::vi::ControlScheme;
+
+pub const DEFAULT_SPEED: u8 = 5;
+pub const MAX_SPEED: u8 = 10;
+pub const DEFAULT_SWIPE_THRESHOLD: f64 = 30.0;
+pub const DEFAULT_VOLUME: f64 = 0.5;
+
+const SWIPE_THRESHOLD_KEY: &str = "swipe-threshold";
+const CONTROL_SCHEME_KEY: &str = "control-scheme";
+const THEME_KEY: &str = "theme";
+const ANNOUNCE_TURNS_KEY: &str = "announce-turns";
+const VOLUME_KEY: &str = "volume";
+const MUTED_KEY: &str = "muted";
+const MUSIC_KEY: &str = "music";
+const GHOST_KEY: &str = "ghost";
+const SHARE_PLAYER_ID_KEY: &str = "share-player-id";
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Settings {
+    /// How fast the snake moves, from 1 (slowest) to `MAX_SPEED`
+    pub speed: u8,
+    pub wrap: bool,
+    /// Seed used for placing apples in new games. A random one is used if none is set.
+    pub seed: Option<u64>,
+    /// How far, in css pixels, a pointer must move on the canvas to count as a swipe
+    pub swipe_threshold: f64,
+    pub control_scheme: ControlScheme,
+    pub theme: ThemeName,
+    /// Tell screen reader users every time the snake turns
+    pub announce_turns: bool,
+    /// Loudness of sound effects and music, from 0 to 1
+    pub volume: f64,
+    pub muted: bool,
+    /// Play a background loop while playing
+    pub music: bool,
+    /// Race against a ghost of your best game, on the same seed
+    pub ghost: bool,
+    /// Send an anonymous player id along with highscores
+    pub share_player_id: bool,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            speed: DEFAULT_SPEED,
+            wrap: false,
+            seed: None,
+            swipe_threshold: DEFAULT_SWIPE_THRESHOLD,
+            control_scheme: ControlScheme::default(),
+            theme: ThemeName::default(),
+            announce_turns: false,
+            volume: DEFAULT_VOLUME,
+            muted: false,
+            music: false,
+            ghost: false,
+            share_player_id: false,
+        }
+    }
+}
+
+impl Settings {
+    /// Default settings, except for the ones saved on the settings screen
+    pub fn load() -> Self {
+        Self {
+            swipe_threshold: storage::load(SWIPE_THRESHOLD_KEY).unwrap_or(DEFAULT_SWIPE_THRESHOLD),
+            control_scheme: storage::load(CONTROL_SCHEME_KEY).unwrap_or_default(),
+            theme: storage::load(THEME_KEY).unwrap_or_default(),
+            announce_turns: storage::load(ANNOUNCE_TURNS_KEY).unwrap_or_default(),
+            volume: storage::load(VOLUME_KEY).unwrap_or(DEFAULT_VOLUME),
+            muted: storage::load(MUTED_KEY).unwrap_or_default(),
+            music: storage::load(MUSIC_KEY).unwrap_or_default(),
+            ghost: storage::load(GHOST_KEY).unwrap_or_default(),
+            share_player_id: storage::load(SHARE_PLAYER_ID_KEY).unwrap_or_default(),
+            ..Self::default()
+        }
+    }
+
+    pub fn save_swipe_threshold(&self) -> Result<(), JsValue> {
+        storage::save(SWIPE_THRESHOLD_KEY, &self.swipe_threshold)
+    }
+
+    pub fn save_control_scheme(&self) -> Result<(), JsValue> {
+        storage::save(CONTROL_SCHEME_KEY, &self.control_scheme)
+    }
+
+    pub fn save_theme(&self) -> Result<(), JsValue> {
+        storage::save(THEME_KEY, &self.theme)
+    }
+
+    pub fn save_announce_turns(&self) -> Result<(), JsValue> {
+        storage::save(ANNOUNCE_TURNS_KEY, &self.announce_turns)
+    }
+
+    pub fn save_volume(&self) -> Result<(), JsValue> {
+        storage::save(VOLUME_KEY, &self.volume)
+    }
+
+    pub fn save_muted(&self) -> Result<(), JsValue> {
+        storage::save(MUTED_KEY, &self.muted)
+    }
+
+    pub fn save_music(&self) -> Result<(), JsValue> {
+        storage::save(MUSIC_KEY, &self.music)
+    }
+
+    pub fn save_ghost(&self) -> Result<(), JsValue> {
+        storage::save(GHOST_KEY, &self.ghost)
+    }
+
+    pub fn save_share_player_id(&self) -> Result<(), JsValue> {
+        storage::save(SHARE_PLAYER_ID_KEY, &self.share_player_id)
+    }
+
+    /// Milliseconds between each step the snake takes
+    pub fn tick_millis(&self) -> u32 {
+        550 - 50 * u32::from(self.speed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_speed_should_step_every_300_millis() {
+        assert_eq!(300, Settings::default().tick_millis());
+    }
+
+    #[test]
+    fn max_speed_should_be_faster_than_default() {
+        let fast = Settings {
+            speed: MAX_SPEED,
+            ..Settings::default()
+        };
+        assert!(fast.tick_millis() < Settings::default().tick_millis());
+    }
+}
@@ -0,0 +1,409 @@
+//! Settings screen for changing preferences which are kept between visits
+use std::cell::Cell;
+use std::rc::Rc;
+use std::sync::{Arc, RwLock};
+
+use gloo_events::EventListener;
+use js_sys::Error;
+use wasm_bindgen::{JsCast, JsValue};
+use web_sys::{Document, Element, HtmlElement, HtmlInputElement, HtmlSelectElement, KeyboardEvent};
+
+use crate::keymap::{self, Action, Keymap};
+use crate::settings::{Settings, DEFAULT_SWIPE_THRESHOLD};
+use crate::sound::Sound;
+use crate::theme::{self, ThemeName};
+use crate::vi::ControlScheme;
+
+/// Fills the settings element with controls for editing the keymap, control scheme, theme,
+/// sound, ghost, highscores, accessibility and touch controls
+pub fn new(
+    doc: &Document,
+    keymap: Arc<RwLock<Keymap>>,
+    settings: Arc<RwLock<Settings>>,
+    sound: Sound,
+) -> Result<(), JsValue> {
+    new_control_scheme(doc, Arc::clone(&settings))?;
+    new_theme(doc, Arc::clone(&settings))?;
+    new_announce_turns(doc, Arc::clone(&settings))?;
+    new_sound(doc, Arc::clone(&settings), sound)?;
+    new_ghost(doc, Arc::clone(&settings))?;
+    new_share_player_id(doc, Arc::clone(&settings))?;
+    new_swipe_threshold(doc, settings)?;
+
+    let table: HtmlElement = doc
+        .query_selector("#keymap tbody")?
+        .map(JsCast::dyn_into)
+        .ok_or_else(|| Error::new("Document had no keymap table"))??;
+
+    let reset_button: HtmlElement = doc
+        .query_selector("#keymap-reset")?
+        .map(JsCast::dyn_into)
+        .ok_or_else(|| Error::new("Document had no keymap reset button"))??;
+
+    render_keymap(doc, &table, &keymap.read().unwrap(), None)?;
+
+    // The action waiting for a key to be pressed, after clicking its add button
+    let capturing = Rc::new(Cell::new(None::<Action>));
+
+    let on_click = {
+        let doc = doc.clone();
+        let table = table.clone();
+        let keymap = Arc::clone(&keymap);
+        let capturing = Rc::clone(&capturing);
+
+        move |event: &web_sys::Event| {
+            let Some(button) = event
+                .target()
+                .and_then(|t| t.dyn_into::<Element>().ok())
+                .and_then(|el| el.closest("button").ok().flatten())
+            else {
+                return;
+            };
+            let Some(action) = button
+                .get_attribute("data-action")
+                .and_then(|a| Action::from_name(&a))
+            else {
+                return;
+            };
+
+            let mut keymap = keymap.write().unwrap();
+            let result = match button.get_attribute("data-key") {
+                Some(key) => {
+                    capturing.set(None);
+                    keymap.unbind(action, &key)
+                }
+                None => {
+                    capturing.set(Some(action));
+                    Ok(())
+                }
+            };
+            update(&doc, &table, &keymap, capturing.get(), result);
+        }
+    };
+    EventListener::new(&table, "click", on_click).forget();
+
+    let on_keydown = {
+        let doc = doc.clone();
+        let table = table.clone();
+        let keymap = Arc::clone(&keymap);
+        let capturing = Rc::clone(&capturing);
+
+        move |event: &web_sys::Event| {
+            let Some(action) = capturing.take() else {
+                return;
+            };
+            let event = event.dyn_ref::<KeyboardEvent>().unwrap();
+            // Don't let the game react to the key being bound
+            event.stop_propagation();
+            event.prevent_default();
+
+            let mut keymap = keymap.write().unwrap();
+            let result = match event.key().as_str() {
+                "Escape" => Ok(()),
+                key => keymap.bind(action, key),
+            };
+            update(&doc, &table, &keymap, None, result);
+        }
+    };
+    EventListener::new(&table, "keydown", on_keydown).forget();
+
+    let on_reset = {
+        let doc = doc.clone();
+        move |_: &web_sys::Event| {
+            let mut keymap = keymap.write().unwrap();
+            *keymap = Keymap::default();
+            capturing.set(None);
+            update(&doc, &table, &keymap, None, Ok(()));
+        }
+    };
+    EventListener::new(&reset_button, "click", on_reset).forget();
+
+    Ok(())
+}
+
+fn new_control_scheme(doc: &Document, settings: Arc<RwLock<Settings>>) -> Result<(), JsValue> {
+    let select: HtmlSelectElement = doc
+        .query_selector("#control-scheme")?
+        .map(JsCast::dyn_into)
+        .ok_or_else(|| Error::new("Document had no control scheme select"))??;
+
+    let scheme = settings.read().unwrap().control_scheme;
+    select.set_value(control_scheme_value(scheme));
+
+    let on_change = {
+        let select = select.clone();
+        move |_: &web_sys::Event| {
+            let mut settings = settings.write().unwrap();
+            settings.control_scheme = match select.value().as_str() {
+                "relative" => ControlScheme::Relative,
+                _ => ControlScheme::Absolute,
+            };
+            settings
+                .save_control_scheme()
+                .unwrap_or_else(|e| log::error!("Failed to save control scheme due to {e:?}"));
+        }
+    };
+    EventListener::new(&select, "change", on_change).forget();
+
+    Ok(())
+}
+
+fn control_scheme_value(scheme: ControlScheme) -> &'static str {
+    match scheme {
+        ControlScheme::Absolute => "absolute",
+        ControlScheme::Relative => "relative",
+    }
+}
+
+/// Shows a control scheme which was changed elsewhere, like on the command line
+pub fn show_control_scheme(doc: &Document, scheme: ControlScheme) -> Result<(), JsValue> {
+    let select: HtmlSelectElement = doc
+        .query_selector("#control-scheme")?
+        .map(JsCast::dyn_into)
+        .ok_or_else(|| Error::new("Document had no control scheme select"))??;
+    select.set_value(control_scheme_value(scheme));
+    Ok(())
+}
+
+fn new_theme(doc: &Document, settings: Arc<RwLock<Settings>>) -> Result<(), JsValue> {
+    let select: HtmlSelectElement = doc
+        .query_selector("#theme")?
+        .map(JsCast::dyn_into)
+        .ok_or_else(|| Error::new("Document had no theme select"))??;
+
+    for theme in ThemeName::ALL {
+        let option = doc.create_element("option")?;
+        option.set_attribute("value", theme.name())?;
+        option.set_text_content(Some(theme.description()));
+        select.append_child(&option)?;
+    }
+    select.set_value(settings.read().unwrap().theme.name());
+
+    let on_change = {
+        let doc = doc.clone();
+        let select = select.clone();
+        move |_: &web_sys::Event| {
+            let mut settings = settings.write().unwrap();
+            settings.theme = ThemeName::from_name(&select.value()).unwrap_or_default();
+            theme::apply_to_page(&doc, settings.theme)
+                .unwrap_or_else(|e| log::error!("Failed to apply theme due to {e:?}"));
+            settings
+                .save_theme()
+                .unwrap_or_else(|e| log::error!("Failed to save theme due to {e:?}"));
+        }
+    };
+    EventListener::new(&select, "change", on_change).forget();
+
+    Ok(())
+}
+
+fn new_announce_turns(doc: &Document, settings: Arc<RwLock<Settings>>) -> Result<(), JsValue> {
+    let checkbox: HtmlInputElement = doc
+        .query_selector("#announce-turns")?
+        .map(JsCast::dyn_into)
+        .ok_or_else(|| Error::new("Document had no announce turns checkbox"))??;
+
+    checkbox.set_checked(settings.read().unwrap().announce_turns);
+
+    let on_change = {
+        let checkbox = checkbox.clone();
+        move |_: &web_sys::Event| {
+            let mut settings = settings.write().unwrap();
+            settings.announce_turns = checkbox.checked();
+            settings
+                .save_announce_turns()
+                .unwrap_or_else(|e| log::error!("Failed to save announce turns due to {e:?}"));
+        }
+    };
+    EventListener::new(&checkbox, "change", on_change).forget();
+
+    Ok(())
+}
+
+fn new_ghost(doc: &Document, settings: Arc<RwLock<Settings>>) -> Result<(), JsValue> {
+    let checkbox: HtmlInputElement = doc
+        .query_selector("#ghost")?
+        .map(JsCast::dyn_into)
+        .ok_or_else(|| Error::new("Document had no ghost checkbox"))??;
+
+    checkbox.set_checked(settings.read().unwrap().ghost);
+
+    let on_change = {
+        let checkbox = checkbox.clone();
+        move |_: &web_sys::Event| {
+            let mut settings = settings.write().unwrap();
+            settings.ghost = checkbox.checked();
+            settings
+                .save_ghost()
+                .unwrap_or_else(|e| log::error!("Failed to save ghost due to {e:?}"));
+        }
+    };
+    EventListener::new(&checkbox, "change", on_change).forget();
+
+    Ok(())
+}
+
+fn new_share_player_id(doc: &Document, settings: Arc<RwLock<Settings>>) -> Result<(), JsValue> {
+    let checkbox: HtmlInputElement = doc
+        .query_selector("#share-player-id")?
+        .map(JsCast::dyn_into)
+        .ok_or_else(|| Error::new("Document had no share player id checkbox"))??;
+
+    checkbox.set_checked(settings.read().unwrap().share_player_id);
+
+    let on_change = {
+        let checkbox = checkbox.clone();
+        move |_: &web_sys::Event| {
+            let mut settings = settings.write().unwrap();
+            settings.share_player_id = checkbox.checked();
+            settings
+                .save_share_player_id()
+                .unwrap_or_else(|e| log::error!("Failed to save share player id due to {e:?}"));
+        }
+    };
+    EventListener::new(&checkbox, "change", on_change).forget();
+
+    Ok(())
+}
+
+fn new_sound(doc: &Document, settings: Arc<RwLock<Settings>>, sound: Sound) -> Result<(), JsValue> {
+    let volume: HtmlInputElement = doc
+        .query_selector("#volume")?
+        .map(JsCast::dyn_into)
+        .ok_or_else(|| Error::new("Document had no volume slider"))??;
+    let music: HtmlInputElement = doc
+        .query_selector("#music")?
+        .map(JsCast::dyn_into)
+        .ok_or_else(|| Error::new("Document had no music checkbox"))??;
+
+    volume.set_value_as_number(settings.read().unwrap().volume * 100.0);
+    music.set_checked(settings.read().unwrap().music);
+
+    let on_volume_change = {
+        let settings = Arc::clone(&settings);
+        let volume = volume.clone();
+        move |_: &web_sys::Event| {
+            let mut settings = settings.write().unwrap();
+            settings.volume = (volume.value_as_number() / 100.0).clamp(0.0, 1.0);
+            settings
+                .save_volume()
+                .unwrap_or_else(|e| log::error!("Failed to save volume due to {e:?}"));
+        }
+    };
+    EventListener::new(&volume, "change", on_volume_change).forget();
+
+    let on_music_change = {
+        let music = music.clone();
+        move |_: &web_sys::Event| {
+            let mut settings = settings.write().unwrap();
+            settings.music = music.checked();
+            settings
+                .save_music()
+                .unwrap_or_else(|e| log::error!("Failed to save music due to {e:?}"));
+            if !settings.music {
+                sound.stop_music();
+            }
+        }
+    };
+    EventListener::new(&music, "change", on_music_change).forget();
+
+    Ok(())
+}
+
+fn new_swipe_threshold(doc: &Document, settings: Arc<RwLock<Settings>>) -> Result<(), JsValue> {
+    let input: HtmlInputElement = doc
+        .query_selector("#swipe-threshold")?
+        .map(JsCast::dyn_into)
+        .ok_or_else(|| Error::new("Document had no swipe threshold input"))??;
+
+    input.set_value_as_number(settings.read().unwrap().swipe_threshold);
+
+    let on_change = {
+        let input = input.clone();
+        move |_: &web_sys::Event| {
+            let threshold = input.value_as_number();
+            let threshold = if threshold.is_finite() {
+                threshold.clamp(5.0, 1000.0)
+            } else {
+                DEFAULT_SWIPE_THRESHOLD
+            };
+            input.set_value_as_number(threshold);
+
+            let mut settings = settings.write().unwrap();
+            settings.swipe_threshold = threshold;
+            settings
+                .save_swipe_threshold()
+                .unwrap_or_else(|e| log::error!("Failed to save swipe threshold due to {e:?}"));
+        }
+    };
+    EventListener::new(&input, "change", on_change).forget();
+
+    Ok(())
+}
+
+fn update(
+    doc: &Document,
+    table: &HtmlElement,
+    keymap: &Keymap,
+    capturing: Option<Action>,
+    result: Result<(), keymap::KeymapError>,
+) {
+    let error = match result {
+        Ok(()) => keymap.save().err().map(|e| {
+            log::error!("Failed to save keymap due to {e:?}");
+            String::from("Failed to save key bindings")
+        }),
+        Err(e) => Some(e.to_string()),
+    };
+
+    if let Some(error_element) = doc.get_element_by_id("keymap-error") {
+        error_element.set_text_content(error.as_deref());
+    }
+
+    render_keymap(doc, table, keymap, capturing)
+        .unwrap_or_else(|e| log::error!("Failed to render keymap due to {e:?}"));
+}
+
+fn render_keymap(
+    doc: &Document,
+    table: &HtmlElement,
+    keymap: &Keymap,
+    capturing: Option<Action>,
+) -> Result<(), JsValue> {
+    table.set_text_content(None);
+
+    for action in Action::ALL {
+        let row = doc.create_element("tr")?;
+        let name = doc.create_element("th")?;
+        name.set_text_content(Some(action.name()));
+        row.append_child(&name)?;
+
+        let keys = doc.create_element("td")?;
+        for key in keymap.keys(action) {
+            let remove = doc.create_element("button")?;
+            remove.set_attribute("data-action", action.name())?;
+            remove.set_attribute("data-key", key)?;
+            remove.set_attribute("title", "Remove")?;
+            remove.set_text_content(Some(&format!("{} ✕", keymap::display_key(key))));
+            keys.append_child(&remove)?;
+        }
+
+        let add = doc.create_element("button")?;
+        add.set_attribute("data-action", action.name())?;
+        add.set_text_content(Some(if capturing == Some(action) {
+            "press a key…"
+        } else {
+            "+"
+        }));
+        keys.append_child(&add)?;
+        row.append_child(&keys)?;
+
+        table.append_child(&row)?;
+
+        if capturing == Some(action) {
+            add.dyn_into::<HtmlElement>()?.focus()?;
+        }
+    }
+
+    Ok(())
+}
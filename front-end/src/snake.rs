@@ -1,6 +1,12 @@
 pub const WIDTH: u32 = 300;
 pub const HEIGHT: u32 = 300;
 pub const LINE_THICKNESS: f64 = 25.0;
+pub const COLUMNS: u32 = WIDTH / LINE_THICKNESS as u32;
+pub const ROWS: u32 = HEIGHT / LINE_THICKNESS as u32;
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
 
 #[derive(Clone, Debug)]
 pub struct Snake {
@@ -8,37 +14,35 @@ pub struct Snake {
     pub direction: Direction,
     pub target: Option<Position>,
     pub alive: bool,
+    /// Go through the walls and come out on the opposite side instead of crashing
+    pub wrap: bool,
+    rng: StdRng,
 }
 
 impl Snake {
     pub fn new() -> Snake {
+        Snake::with_seed(rand::random())
+    }
+
+    /// Creates a snake whose apples are placed in the same order every time for the same seed
+    pub fn with_seed(seed: u64) -> Snake {
+        let mut rng = StdRng::seed_from_u64(seed);
         Snake {
             body: vec![Position { x: 0.0, y: 0.0 }],
             direction: Direction::Right,
-            target: Some(Position::random()),
+            target: Some(Position::random(&mut rng)),
             alive: true,
+            wrap: false,
+            rng,
         }
     }
 
     fn next_position(&self) -> Position {
-        let head = self.head();
-        match self.direction {
-            Direction::Up => Position {
-                y: head.y - LINE_THICKNESS,
-                ..*head
-            },
-            Direction::Right => Position {
-                x: head.x + LINE_THICKNESS,
-                ..*head
-            },
-            Direction::Down => Position {
-                y: head.y + LINE_THICKNESS,
-                ..*head
-            },
-            Direction::Left => Position {
-                x: head.x - LINE_THICKNESS,
-                ..*head
-            },
+        let next = self.head().step(self.direction);
+        if self.wrap {
+            next.wrapped()
+        } else {
+            next
         }
     }
 
@@ -69,10 +73,11 @@ impl Snake {
         }
 
         let new_head = self.next_position();
+        let mut rng = self.rng.clone();
         let (dropped, target, body) = if Some(new_head) == self.target {
             let mut body = self.body.clone();
             body.push(new_head);
-            (None, Position::random_except(&body), body)
+            (None, Position::random_except(&body, &mut rng), body)
         } else {
             let mut body = self.body.iter().skip(1).copied().collect::<Vec<_>>();
             body.push(new_head);
@@ -83,6 +88,7 @@ impl Snake {
             Snake {
                 body,
                 target,
+                rng,
                 ..*self
             },
             dropped,
@@ -90,13 +96,24 @@ impl Snake {
     }
 
     fn dying(&self) -> bool {
+        self.collision().is_some()
+    }
+
+    /// What the snake would crash into if it took another step, if anything
+    pub fn collision(&self) -> Option<Collision> {
         let next_pos = self.next_position();
-        !next_pos.is_inside_walls()
-            || self
-                .body
-                .iter()
-                .skip(if Some(next_pos) == self.target { 0 } else { 1 })
-                .any(|p| *p == next_pos)
+        if !next_pos.is_inside_walls() {
+            Some(Collision::Wall)
+        } else if self
+            .body
+            .iter()
+            .skip(if Some(next_pos) == self.target { 0 } else { 1 })
+            .any(|p| *p == next_pos)
+        {
+            Some(Collision::Itself)
+        } else {
+            None
+        }
     }
 
     pub fn kill(&self) -> Snake {
@@ -108,6 +125,12 @@ impl Snake {
 }
 
 #[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Collision {
+    Wall,
+    Itself,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum Direction {
     Up,
     Right,
@@ -124,6 +147,26 @@ impl Direction {
             Direction::Left => Direction::Right,
         }
     }
+
+    /// Rotates 90 degrees counter-clockwise
+    pub fn turn_left(self) -> Direction {
+        match self {
+            Direction::Up => Direction::Left,
+            Direction::Right => Direction::Up,
+            Direction::Down => Direction::Right,
+            Direction::Left => Direction::Down,
+        }
+    }
+
+    /// Rotates 90 degrees clockwise
+    pub fn turn_right(self) -> Direction {
+        match self {
+            Direction::Up => Direction::Right,
+            Direction::Right => Direction::Down,
+            Direction::Down => Direction::Left,
+            Direction::Left => Direction::Up,
+        }
+    }
 }
 
 #[derive(Copy, Clone, Debug, PartialEq)]
@@ -133,23 +176,53 @@ pub struct Position {
 }
 
 impl Position {
-    fn random() -> Position {
-        let mut x = (rand::random::<f64>() * f64::from(WIDTH)).floor();
-        let mut y = (rand::random::<f64>() * f64::from(HEIGHT)).floor();
+    /// The position one step away in the given direction
+    pub fn step(&self, direction: Direction) -> Position {
+        match direction {
+            Direction::Up => Position {
+                y: self.y - LINE_THICKNESS,
+                ..*self
+            },
+            Direction::Right => Position {
+                x: self.x + LINE_THICKNESS,
+                ..*self
+            },
+            Direction::Down => Position {
+                y: self.y + LINE_THICKNESS,
+                ..*self
+            },
+            Direction::Left => Position {
+                x: self.x - LINE_THICKNESS,
+                ..*self
+            },
+        }
+    }
+
+    pub fn column(&self) -> u32 {
+        (self.x / LINE_THICKNESS).round() as u32
+    }
+
+    pub fn row(&self) -> u32 {
+        (self.y / LINE_THICKNESS).round() as u32
+    }
+
+    fn random(rng: &mut impl Rng) -> Position {
+        let mut x = (rng.random::<f64>() * f64::from(WIDTH)).floor();
+        let mut y = (rng.random::<f64>() * f64::from(HEIGHT)).floor();
         // we substract val % LINE_THICKNESS so the snake can get here
         x -= x % LINE_THICKNESS;
         y -= y % LINE_THICKNESS;
         Position { x, y }
     }
 
-    fn random_except(blacklist: &[Position]) -> Option<Position> {
-        let max_positions = WIDTH / LINE_THICKNESS as u32 * HEIGHT / LINE_THICKNESS as u32;
+    fn random_except(blacklist: &[Position], rng: &mut impl Rng) -> Option<Position> {
+        let max_positions = COLUMNS * ROWS;
         // TODO: Maybe don't do completely random when there are only a few options
         if blacklist.len() as u32 == max_positions {
             return None;
         }
         loop {
-            let random = Position::random();
+            let random = Position::random(rng);
             if blacklist.iter().all(|p| *p != random) {
                 return Some(random);
             }
@@ -162,6 +235,25 @@ impl Position {
             && self.y.round() as i32 >= 0
             && (self.y.round() as u32) < HEIGHT
     }
+
+    /// Which way to step to get to a neighbouring position, including through the walls
+    pub fn direction_to(&self, other: &Position) -> Option<Direction> {
+        [
+            Direction::Up,
+            Direction::Right,
+            Direction::Down,
+            Direction::Left,
+        ]
+        .into_iter()
+        .find(|dir| self.step(*dir).wrapped() == *other)
+    }
+
+    fn wrapped(&self) -> Position {
+        Position {
+            x: self.x.rem_euclid(f64::from(WIDTH)),
+            y: self.y.rem_euclid(f64::from(HEIGHT)),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -198,7 +290,7 @@ mod tests {
             ..Snake::new()
         };
 
-        assert!(snake.dying());
+        assert_eq!(Some(Collision::Wall), snake.collision());
         let (snake, _) = snake.move_along();
         assert!(!snake.alive);
     }
@@ -217,7 +309,7 @@ mod tests {
             ..Snake::new()
         };
 
-        assert!(snake.dying());
+        assert_eq!(Some(Collision::Itself), snake.collision());
         let (snake, _) = snake.move_along();
         assert!(!snake.alive)
     }
@@ -231,7 +323,7 @@ mod tests {
                 Position { x: 25.0, y: 25.0 },
                 Position { x: 0.0, y: 25.0 },
             ],
-            target: Position::random_except(&[Position { x: 0.0, y: 0.0 }]),
+            target: Position::random_except(&[Position { x: 0.0, y: 0.0 }], &mut rand::rng()),
             direction: Direction::Up,
             ..Snake::new()
         };
@@ -348,8 +440,81 @@ mod tests {
         assert_eq!(max_length, snake.body.len() as u32);
     }
 
+    #[test]
+    fn it_comes_out_on_the_other_side_when_crashing_into_wall_with_wrap() {
+        let snake = Snake {
+            direction: Direction::Left,
+            body: vec![Position { x: 0.0, y: 0.0 }],
+            wrap: true,
+            ..Snake::new()
+        };
+
+        assert!(!snake.dying());
+        let (snake, _) = snake.move_along();
+        assert!(snake.alive);
+        assert_eq!(
+            Position {
+                x: f64::from(WIDTH) - LINE_THICKNESS,
+                y: 0.0
+            },
+            *snake.head()
+        );
+    }
+
+    #[test]
+    fn it_places_apples_the_same_way_given_the_same_seed() {
+        let a = Snake::with_seed(1234);
+        let b = Snake::with_seed(1234);
+        assert_eq!(a.target, b.target);
+    }
+
     #[test]
     fn direction_turn_180_degrees_given_up_should_be_down() {
         assert_eq!(Direction::Up, Direction::Down.turn_180_degrees());
     }
+
+    #[test]
+    fn direction_turn_left_given_up_should_be_left() {
+        assert_eq!(Direction::Left, Direction::Up.turn_left());
+    }
+
+    #[test]
+    fn direction_turn_right_given_left_should_be_up() {
+        assert_eq!(Direction::Up, Direction::Left.turn_right());
+    }
+
+    #[test]
+    fn direction_turning_left_then_right_should_be_no_turn() {
+        for dir in [
+            Direction::Up,
+            Direction::Right,
+            Direction::Down,
+            Direction::Left,
+        ] {
+            assert_eq!(dir, dir.turn_left().turn_right());
+            assert_eq!(dir.turn_180_degrees(), dir.turn_right().turn_right());
+        }
+    }
+
+    #[test]
+    fn direction_to_should_find_neighbours() {
+        let pos = Position { x: 25.0, y: 25.0 };
+        assert_eq!(
+            Some(Direction::Up),
+            pos.direction_to(&Position { x: 25.0, y: 0.0 })
+        );
+        assert_eq!(None, pos.direction_to(&Position { x: 75.0, y: 25.0 }));
+    }
+
+    #[test]
+    fn direction_to_should_go_through_walls() {
+        let pos = Position { x: 0.0, y: 0.0 };
+        assert_eq!(
+            Some(Direction::Left),
+            pos.direction_to(&Position {
+                x: f64::from(WIDTH) - LINE_THICKNESS,
+                y: 0.0
+            })
+        );
+    }
 }
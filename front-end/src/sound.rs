@@ -0,0 +1,202 @@
+//! Retro beeps synthesized with the Web Audio API
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::sync::{Arc, RwLock};
+
+use gloo_timers::callback::Interval;
+use wasm_bindgen::JsValue;
+use web_sys::{AudioContext, GainNode, OscillatorType};
+
+use crate::game::Effect;
+use crate::settings::Settings;
+use crate::GameStatus;
+
+/// A tone, with frequency in Hz and duration in seconds. A frequency of 0 is a rest.
+type Note = (f32, f64);
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Sfx {
+    Start,
+    Apple,
+    Turn,
+    Death,
+    Highscore,
+}
+
+impl Sfx {
+    /// What to play for a game event, if anything
+    pub fn for_effect(effect: &Effect) -> Option<Sfx> {
+        match effect {
+            Effect::NewGame => Some(Sfx::Start),
+            Effect::AteApple(_) => Some(Sfx::Apple),
+            Effect::Turned(_) => Some(Sfx::Turn),
+            Effect::GameOver { .. } => Some(Sfx::Death),
+            _ => None,
+        }
+    }
+
+    fn notes(self) -> &'static [Note] {
+        match self {
+            Sfx::Start => &[(440.0, 0.08), (554.37, 0.08), (659.25, 0.12)],
+            Sfx::Apple => &[(880.0, 0.05), (1318.51, 0.07)],
+            Sfx::Turn => &[(220.0, 0.03)],
+            Sfx::Death => &[(392.0, 0.15), (329.63, 0.15), (261.63, 0.35)],
+            Sfx::Highscore => &[(523.25, 0.1), (659.25, 0.1), (783.99, 0.1), (1046.5, 0.3)],
+        }
+    }
+
+    fn wave(self) -> OscillatorType {
+        match self {
+            Sfx::Death => OscillatorType::Sawtooth,
+            _ => OscillatorType::Square,
+        }
+    }
+}
+
+/// The background loop, played over and over while the game is on
+const MUSIC: [Note; 8] = [
+    (110.0, 0.25),
+    (0.0, 0.25),
+    (164.81, 0.25),
+    (0.0, 0.25),
+    (146.83, 0.25),
+    (0.0, 0.25),
+    (130.81, 0.25),
+    (123.47, 0.25),
+];
+
+fn duration(notes: &[Note]) -> f64 {
+    notes.iter().map(|(_, length)| length).sum()
+}
+
+/// Created on first use, because browsers only allow audio after the user did something
+struct Output {
+    context: AudioContext,
+    /// Everything goes through this, so it controls the volume
+    master: GainNode,
+}
+
+#[derive(Clone)]
+pub struct Sound {
+    settings: Arc<RwLock<Settings>>,
+    output: Rc<RefCell<Option<Output>>>,
+    music: Rc<RefCell<Option<Interval>>>,
+}
+
+impl Sound {
+    pub fn new(settings: Arc<RwLock<Settings>>) -> Self {
+        Self {
+            settings,
+            output: Rc::new(RefCell::new(None)),
+            music: Rc::new(RefCell::new(None)),
+        }
+    }
+
+    /// Reacts to a game event with a sound effect, or by starting or stopping the music
+    pub fn update(&self, effect: &Effect) -> Result<(), JsValue> {
+        match effect {
+            Effect::Status(GameStatus::Playing) => self.start_music()?,
+            Effect::Status(_) | Effect::GameOver { .. } => self.stop_music(),
+            Effect::Muted(_) => self.set_volume(),
+            _ => {}
+        }
+        match Sfx::for_effect(effect) {
+            Some(sfx) => self.play(sfx),
+            None => Ok(()),
+        }
+    }
+
+    pub fn play(&self, sfx: Sfx) -> Result<(), JsValue> {
+        self.schedule(sfx.notes(), sfx.wave(), 0.3)
+    }
+
+    fn start_music(&self) -> Result<(), JsValue> {
+        if !self.settings.read().unwrap().music || self.music.borrow().is_some() {
+            return Ok(());
+        }
+
+        self.schedule(&MUSIC, OscillatorType::Triangle, 0.2)?;
+        let sound = self.clone();
+        let bar_millis = (duration(&MUSIC) * 1000.0) as u32;
+        *self.music.borrow_mut() = Some(Interval::new(bar_millis, move || {
+            sound
+                .schedule(&MUSIC, OscillatorType::Triangle, 0.2)
+                .unwrap_or_else(|e| log::error!("Failed to play music due to {e:?}"));
+        }));
+        Ok(())
+    }
+
+    pub fn stop_music(&self) {
+        // Dropping the interval cancels it
+        self.music.borrow_mut().take();
+    }
+
+    fn set_volume(&self) {
+        if let Some(output) = self.output.borrow().as_ref() {
+            let settings = self.settings.read().unwrap();
+            let volume = if settings.muted { 0.0 } else { settings.volume };
+            output.master.gain().set_value(volume as f32);
+        }
+    }
+
+    /// Plays the notes one after another, starting now
+    fn schedule(&self, notes: &[Note], wave: OscillatorType, level: f32) -> Result<(), JsValue> {
+        let settings = *self.settings.read().unwrap();
+        if settings.muted || settings.volume <= 0.0 {
+            return Ok(());
+        }
+
+        if self.output.borrow().is_none() {
+            let context = AudioContext::new()?;
+            let master = context.create_gain()?;
+            master.connect_with_audio_node(&context.destination())?;
+            *self.output.borrow_mut() = Some(Output { context, master });
+        }
+        self.set_volume();
+
+        let output = self.output.borrow();
+        let Output { context, master } = output.as_ref().unwrap();
+        let mut start = context.current_time();
+        for &(frequency, length) in notes {
+            if frequency > 0.0 {
+                let oscillator = context.create_oscillator()?;
+                oscillator.set_type(wave);
+                oscillator.frequency().set_value(frequency);
+
+                // Fade out each note, so it ends without a click
+                let envelope = context.create_gain()?;
+                envelope.gain().set_value_at_time(level, start)?;
+                envelope
+                    .gain()
+                    .exponential_ramp_to_value_at_time(0.001, start + length)?;
+
+                oscillator.connect_with_audio_node(&envelope)?;
+                envelope.connect_with_audio_node(master)?;
+                oscillator.start_with_when(start)?;
+                oscillator.stop_with_when(start + length)?;
+            }
+            start += length;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::Ending;
+
+    #[test]
+    fn game_events_should_have_sounds() {
+        assert_eq!(Some(Sfx::Start), Sfx::for_effect(&Effect::NewGame));
+        assert_eq!(Some(Sfx::Apple), Sfx::for_effect(&Effect::AteApple(1)));
+        assert_eq!(
+            Some(Sfx::Death),
+            Sfx::for_effect(&Effect::GameOver {
+                apples: 0,
+                ending: Ending::Quit
+            })
+        );
+        assert_eq!(None, Sfx::for_effect(&Effect::AppleCount(1)));
+    }
+}
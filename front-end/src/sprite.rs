@@ -0,0 +1,133 @@
+//! Splits the snake into segments which know which way their neighbours are,
+//! so each part can be drawn with the right shape
+use crate::snake::{Direction, Position, Snake};
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Segment {
+    Head {
+        pos: Position,
+        facing: Direction,
+        /// Where the rest of the body is, unless the snake is only a head
+        neck: Option<Direction>,
+    },
+    Body {
+        pos: Position,
+        towards_tail: Direction,
+        towards_head: Direction,
+    },
+    Tail {
+        pos: Position,
+        towards_head: Direction,
+    },
+}
+
+impl Segment {
+    /// The sides of the cell which join up with the rest of the snake
+    pub fn connections(&self) -> Vec<Direction> {
+        match *self {
+            Segment::Head { neck, .. } => neck.into_iter().collect(),
+            Segment::Body {
+                towards_tail,
+                towards_head,
+                ..
+            } => vec![towards_tail, towards_head],
+            Segment::Tail { towards_head, .. } => vec![towards_head],
+        }
+    }
+}
+
+/// Unit vector pointing in the direction, in board coordinates
+pub fn vector(direction: Direction) -> (f64, f64) {
+    match direction {
+        Direction::Up => (0.0, -1.0),
+        Direction::Right => (1.0, 0.0),
+        Direction::Down => (0.0, 1.0),
+        Direction::Left => (-1.0, 0.0),
+    }
+}
+
+/// The segments from tail to head
+pub fn segments(snake: &Snake) -> Vec<Segment> {
+    let body = &snake.body;
+    let last = body.len() - 1;
+    let towards = |from: usize, to: usize| body[from].direction_to(&body[to]);
+
+    body.iter()
+        .enumerate()
+        .map(|(i, pos)| {
+            if i == last {
+                Segment::Head {
+                    pos: *pos,
+                    facing: snake.direction,
+                    neck: i.checked_sub(1).and_then(|prev| towards(i, prev)),
+                }
+            } else if i == 0 {
+                Segment::Tail {
+                    pos: *pos,
+                    towards_head: towards(0, 1).unwrap_or(snake.direction),
+                }
+            } else {
+                let towards_head = towards(i, i + 1).unwrap_or(snake.direction);
+                Segment::Body {
+                    pos: *pos,
+                    towards_tail: towards(i, i - 1).unwrap_or(towards_head.turn_180_degrees()),
+                    towards_head,
+                }
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snake(cells: &[(f64, f64)], direction: Direction) -> Snake {
+        let mut snake = Snake::with_seed(0);
+        snake.body = cells.iter().map(|&(x, y)| Position { x, y }).collect();
+        snake.direction = direction;
+        snake
+    }
+
+    #[test]
+    fn lone_head_should_have_no_neck() {
+        let segments = segments(&snake(&[(0.0, 0.0)], Direction::Right));
+        assert_eq!(
+            vec![Segment::Head {
+                pos: Position { x: 0.0, y: 0.0 },
+                facing: Direction::Right,
+                neck: None
+            }],
+            segments
+        );
+    }
+
+    #[test]
+    fn segments_should_know_their_neighbours() {
+        // A snake going right, then turning down
+        let segments = segments(&snake(
+            &[(0.0, 0.0), (25.0, 0.0), (25.0, 25.0)],
+            Direction::Down,
+        ));
+
+        assert_eq!(
+            vec![
+                Segment::Tail {
+                    pos: Position { x: 0.0, y: 0.0 },
+                    towards_head: Direction::Right
+                },
+                Segment::Body {
+                    pos: Position { x: 25.0, y: 0.0 },
+                    towards_tail: Direction::Left,
+                    towards_head: Direction::Down
+                },
+                Segment::Head {
+                    pos: Position { x: 25.0, y: 25.0 },
+                    facing: Direction::Down,
+                    neck: Some(Direction::Up)
+                },
+            ],
+            segments
+        );
+    }
+}
@@ -0,0 +1,283 @@
+//! Statistics about the games played in this browser, kept in local storage.
+//! They're shown next to the highscore tables, and can be exported and imported as json.
+use std::collections::BTreeMap;
+
+use gloo_events::EventListener;
+use js_sys::Error;
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::{JsCast, JsValue};
+use wasm_bindgen_futures::{spawn_local, JsFuture};
+use web_sys::{Document, Element, HtmlElement, HtmlInputElement};
+
+use crate::storage;
+
+const STATS_KEY: &str = "stats";
+const EXPORT_FILE_NAME: &str = "snake-stats.json";
+
+/// Describes the rules a game was played with, so bests are only compared between equal games
+pub fn variant(wrap: bool, board: Option<u64>) -> String {
+    let mode = if wrap { "wrap" } else { "walls" };
+    match board {
+        Some(seed) => format!("{mode}, board {seed}"),
+        None => format!("{mode}, random board"),
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Stats {
+    pub games: u32,
+    pub apples: u64,
+    /// How many games ended with each score
+    pub histogram: BTreeMap<u8, u32>,
+    /// Best score for each mode and board, keyed by their `variant`
+    pub best: BTreeMap<String, u8>,
+}
+
+impl Stats {
+    pub fn load() -> Self {
+        storage::load(STATS_KEY).unwrap_or_default()
+    }
+
+    pub fn save(&self) -> Result<(), JsValue> {
+        storage::save(STATS_KEY, self)
+    }
+
+    pub fn record(&mut self, apples: u8, variant: String) {
+        self.games += 1;
+        self.apples += u64::from(apples);
+        *self.histogram.entry(apples).or_default() += 1;
+        let best = self.best.entry(variant).or_default();
+        *best = (*best).max(apples);
+    }
+
+    pub fn best_score(&self) -> Option<u8> {
+        self.histogram.keys().next_back().copied()
+    }
+
+    pub fn average(&self) -> Option<f64> {
+        (self.games > 0).then(|| self.apples as f64 / f64::from(self.games))
+    }
+
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string_pretty(self)
+    }
+
+    /// Reads exported stats, checking that they add up
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        let stats: Self = serde_json::from_str(json).map_err(|e| e.to_string())?;
+        let histogram_games: u64 = stats.histogram.values().map(|&n| u64::from(n)).sum();
+        let histogram_apples: u64 = stats
+            .histogram
+            .iter()
+            .map(|(&score, &n)| u64::from(score) * u64::from(n))
+            .sum();
+        if histogram_games != u64::from(stats.games) || histogram_apples != stats.apples {
+            return Err(String::from(
+                "The games and apples don't match the score histogram",
+            ));
+        }
+        Ok(stats)
+    }
+}
+
+/// Counts a finished game and updates the stats panel
+pub fn record(doc: &Document, apples: u8, variant: String) {
+    let mut stats = Stats::load();
+    stats.record(apples, variant);
+    stats
+        .save()
+        .unwrap_or_else(|e| log::error!("Failed to save stats due to {e:?}"));
+    render(doc, &stats).unwrap_or_else(|e| log::error!("Failed to render stats due to {e:?}"));
+}
+
+/// Fills the stats panel and sets up its export and import buttons
+pub fn new(doc: &Document) -> Result<(), JsValue> {
+    render(doc, &Stats::load())?;
+
+    let export_button = doc
+        .get_element_by_id("stats-export")
+        .ok_or_else(|| Error::new("Document had no stats export button"))?;
+    let on_export = {
+        let doc = doc.clone();
+        move |_: &web_sys::Event| {
+            export(&doc).unwrap_or_else(|e| log::error!("Failed to export stats due to {e:?}"));
+        }
+    };
+    EventListener::new(&export_button, "click", on_export).forget();
+
+    let import_input: HtmlInputElement = doc
+        .get_element_by_id("stats-import")
+        .map(JsCast::dyn_into)
+        .ok_or_else(|| Error::new("Document had no stats import input"))??;
+    let on_import = {
+        let doc = doc.clone();
+        let input = import_input.clone();
+        move |_: &web_sys::Event| {
+            let Some(file) = input.files().and_then(|files| files.get(0)) else {
+                return;
+            };
+            // Lets the same file be picked again
+            input.set_value("");
+            let doc = doc.clone();
+            spawn_local(async move {
+                let result = match JsFuture::from(file.text()).await {
+                    Ok(text) => import(&doc, &text.as_string().unwrap_or_default()),
+                    Err(e) => Err(format!("Unable to read the file: {e:?}")),
+                };
+                set_error(&doc, result.err().as_deref());
+            });
+        }
+    };
+    EventListener::new(&import_input, "change", on_import).forget();
+
+    Ok(())
+}
+
+fn export(doc: &Document) -> Result<(), JsValue> {
+    let json = Stats::load()
+        .to_json()
+        .map_err(|e| Error::new(&e.to_string()))?;
+    let link: HtmlElement = doc.create_element("a")?.dyn_into()?;
+    let href = format!(
+        "data:application/json;charset=utf-8,{}",
+        js_sys::encode_uri_component(&json)
+    );
+    link.set_attribute("href", &href)?;
+    link.set_attribute("download", EXPORT_FILE_NAME)?;
+    link.click();
+    Ok(())
+}
+
+/// Replaces the stats with imported ones
+fn import(doc: &Document, json: &str) -> Result<(), String> {
+    let stats = Stats::from_json(json).map_err(|e| format!("Invalid stats file: {e}"))?;
+    stats.save().map_err(|e| {
+        log::error!("Failed to save imported stats due to {e:?}");
+        String::from("Failed to save the imported stats")
+    })?;
+    render(doc, &stats).unwrap_or_else(|e| log::error!("Failed to render stats due to {e:?}"));
+    Ok(())
+}
+
+fn set_error(doc: &Document, error: Option<&str>) {
+    if let Some(error_element) = doc.get_element_by_id("stats-error") {
+        error_element.set_text_content(error);
+    }
+}
+
+fn element(doc: &Document, selector: &str) -> Result<Element, JsValue> {
+    doc.query_selector(selector)?
+        .ok_or_else(|| Error::new(&format!("Cant find stats element {selector}")).into())
+}
+
+fn row(doc: &Document, cells: &[&str]) -> Result<Element, JsValue> {
+    let row = doc.create_element("tr")?;
+    for text in cells {
+        let cell = doc.create_element("td")?;
+        cell.set_text_content(Some(text));
+        row.append_child(&cell)?;
+    }
+    Ok(row)
+}
+
+/// Text for each line of the summary: games played, best, average and total apples
+fn summary(stats: &Stats) -> [(&'static str, String); 4] {
+    let or_dash = |value: Option<String>| value.unwrap_or_else(|| String::from("-"));
+    [
+        ("Games played", stats.games.to_string()),
+        (
+            "Best score",
+            or_dash(stats.best_score().map(|s| s.to_string())),
+        ),
+        (
+            "Average score",
+            or_dash(stats.average().map(|a| format!("{a:.1}"))),
+        ),
+        ("Total apples", stats.apples.to_string()),
+    ]
+}
+
+fn render(doc: &Document, stats: &Stats) -> Result<(), JsValue> {
+    let summary_list = element(doc, "#stats-summary")?;
+    summary_list.set_text_content(None);
+    for (term, value) in summary(stats) {
+        let dt = doc.create_element("dt")?;
+        dt.set_text_content(Some(term));
+        summary_list.append_child(&dt)?;
+        let dd = doc.create_element("dd")?;
+        dd.set_text_content(Some(&value));
+        summary_list.append_child(&dd)?;
+    }
+
+    let histogram = element(doc, "#stats-histogram tbody")?;
+    histogram.set_text_content(None);
+    let most = stats.histogram.values().copied().max().unwrap_or_default();
+    for (score, &games) in &stats.histogram {
+        let row = row(doc, &[&score.to_string(), &games.to_string()])?;
+        let bar_cell = doc.create_element("td")?;
+        let bar = doc.create_element("div")?;
+        bar.set_class_name("bar");
+        let width = 100.0 * f64::from(games) / f64::from(most);
+        bar.set_attribute("style", &format!("width: {width:.0}%"))?;
+        bar_cell.append_child(&bar)?;
+        row.append_child(&bar_cell)?;
+        histogram.append_child(&row)?;
+    }
+
+    let best = element(doc, "#stats-best tbody")?;
+    best.set_text_content(None);
+    for (variant, score) in &stats.best {
+        let row = row(doc, &[variant, &score.to_string()])?;
+        best.append_child(&row)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn games_should_be_counted_per_score_and_variant() {
+        let mut stats = Stats::default();
+        stats.record(3, variant(false, None));
+        stats.record(5, variant(false, None));
+        stats.record(3, variant(true, Some(42)));
+
+        assert_eq!(3, stats.games);
+        assert_eq!(11, stats.apples);
+        assert_eq!(Some(&2), stats.histogram.get(&3));
+        assert_eq!(Some(5), stats.best_score());
+        assert_eq!(Some(5), stats.best.get("walls, random board").copied());
+        assert_eq!(Some(3), stats.best.get("wrap, board 42").copied());
+        assert!((stats.average().unwrap() - 11.0 / 3.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn no_games_should_have_no_best_or_average() {
+        let stats = Stats::default();
+        assert_eq!(None, stats.best_score());
+        assert_eq!(None, stats.average());
+        assert_eq!("-", summary(&stats)[1].1);
+    }
+
+    #[test]
+    fn exported_stats_should_import_again() {
+        let mut stats = Stats::default();
+        stats.record(7, variant(false, Some(1)));
+        stats.record(0, variant(true, None));
+
+        assert_eq!(
+            Ok(stats.clone()),
+            Stats::from_json(&stats.to_json().unwrap())
+        );
+    }
+
+    #[test]
+    fn import_should_reject_stats_which_dont_add_up() {
+        let json = r#"{"games": 2, "apples": 4, "histogram": {"4": 1}, "best": {}}"#;
+        assert!(Stats::from_json(json).is_err());
+        assert!(Stats::from_json("not json").is_err());
+    }
+}
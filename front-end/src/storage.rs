@@ -0,0 +1,31 @@
+//! Persists values as json in the browser's local storage
+use gloo_utils::window;
+use js_sys::Error;
+use serde::{de::DeserializeOwned, Serialize};
+use wasm_bindgen::JsValue;
+use web_sys::Storage;
+
+fn local_storage() -> Result<Storage, JsValue> {
+    window()
+        .local_storage()?
+        .ok_or_else(|| Error::new("Local storage is not available").into())
+}
+
+/// Loads a previously saved value, or `None` if there is none or it can't be read
+pub fn load<T: DeserializeOwned>(key: &str) -> Option<T> {
+    let json = local_storage()
+        .and_then(|storage| storage.get_item(key))
+        .unwrap_or_else(|e| {
+            log::warn!("Unable to read {key} from local storage due to {e:?}");
+            None
+        })?;
+
+    serde_json::from_str(&json)
+        .inspect_err(|e| log::warn!("Ignoring invalid {key} in local storage due to {e:?}"))
+        .ok()
+}
+
+pub fn save<T: Serialize>(key: &str, value: &T) -> Result<(), JsValue> {
+    let json = serde_json::to_string(value).map_err(|e| Error::new(&e.to_string()))?;
+    local_storage()?.set_item(key, &json)
+}
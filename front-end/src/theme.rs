@@ -0,0 +1,156 @@
+//! Color schemes for the canvas and the page around it
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::{JsCast, JsValue};
+use web_sys::{Document, HtmlElement};
+
+/// Colors as css color strings
+#[derive(Debug, PartialEq)]
+pub struct Theme {
+    pub head: &'static str,
+    pub body: &'static str,
+    pub apple: &'static str,
+    pub text: &'static str,
+    pub background: &'static str,
+    /// Lines between the cells, if they should be drawn
+    pub grid: Option<&'static str>,
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum ThemeName {
+    #[default]
+    Classic,
+    Nokia,
+    Dark,
+    HighContrast,
+    Deuteranopia,
+}
+
+const CLASSIC: Theme = Theme {
+    head: "#abba00",
+    body: "#bada55",
+    apple: "red",
+    text: "blue",
+    background: "white",
+    grid: None,
+};
+
+/// The green LCD of old Nokia phones
+const NOKIA: Theme = Theme {
+    head: "#1e2a10",
+    body: "#2f3f1b",
+    apple: "#1e2a10",
+    text: "#1e2a10",
+    background: "#9bbc0f",
+    grid: Some("#8bac0f"),
+};
+
+const DARK: Theme = Theme {
+    head: "#8fd14f",
+    body: "#5a8f2e",
+    apple: "#ff6b6b",
+    text: "#e0e0e0",
+    background: "#1e1e1e",
+    grid: Some("#2a2a2a"),
+};
+
+const HIGH_CONTRAST: Theme = Theme {
+    head: "yellow",
+    body: "white",
+    apple: "#ff00ff",
+    text: "white",
+    background: "black",
+    grid: None,
+};
+
+/// Blue and orange, which stay apart for people who can't tell red from green
+const DEUTERANOPIA: Theme = Theme {
+    head: "#0047ab",
+    body: "#4f94cd",
+    apple: "#ff8c00",
+    text: "#0047ab",
+    background: "#fffdf5",
+    grid: None,
+};
+
+impl ThemeName {
+    pub const ALL: [ThemeName; 5] = [
+        ThemeName::Classic,
+        ThemeName::Nokia,
+        ThemeName::Dark,
+        ThemeName::HighContrast,
+        ThemeName::Deuteranopia,
+    ];
+
+    /// Identifier used in the settings screen
+    pub fn name(self) -> &'static str {
+        match self {
+            ThemeName::Classic => "classic",
+            ThemeName::Nokia => "nokia",
+            ThemeName::Dark => "dark",
+            ThemeName::HighContrast => "high-contrast",
+            ThemeName::Deuteranopia => "deuteranopia",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<ThemeName> {
+        Self::ALL.into_iter().find(|theme| theme.name() == name)
+    }
+
+    pub fn description(self) -> &'static str {
+        match self {
+            ThemeName::Classic => "Classic",
+            ThemeName::Nokia => "Nokia green LCD",
+            ThemeName::Dark => "Dark",
+            ThemeName::HighContrast => "High contrast",
+            ThemeName::Deuteranopia => "Color-blind friendly (deuteranopia)",
+        }
+    }
+
+    pub fn theme(self) -> &'static Theme {
+        match self {
+            ThemeName::Classic => &CLASSIC,
+            ThemeName::Nokia => &NOKIA,
+            ThemeName::Dark => &DARK,
+            ThemeName::HighContrast => &HIGH_CONTRAST,
+            ThemeName::Deuteranopia => &DEUTERANOPIA,
+        }
+    }
+}
+
+/// Exposes the theme to the stylesheet, as css variables on the root element
+pub fn apply_to_page(doc: &Document, name: ThemeName) -> Result<(), JsValue> {
+    let Some(root) = doc.document_element() else {
+        return Ok(());
+    };
+    root.set_attribute("data-theme", name.name())?;
+
+    let style = root.dyn_into::<HtmlElement>()?.style();
+    let theme = name.theme();
+    style.set_property("--theme-head", theme.head)?;
+    style.set_property("--theme-body", theme.body)?;
+    style.set_property("--theme-apple", theme.apple)?;
+    style.set_property("--theme-text", theme.text)?;
+    style.set_property("--theme-background", theme.background)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_should_round_trip() {
+        for theme in ThemeName::ALL {
+            assert_eq!(Some(theme), ThemeName::from_name(theme.name()));
+        }
+    }
+
+    #[test]
+    fn classic_theme_should_keep_the_original_colors() {
+        let theme = ThemeName::default().theme();
+        assert_eq!("#abba00", theme.head);
+        assert_eq!("#bada55", theme.body);
+        assert_eq!("red", theme.apple);
+        assert_eq!("blue", theme.text);
+    }
+}
@@ -0,0 +1,152 @@
+//! Converts swipes and taps on the canvas to commands
+use std::cell::Cell;
+use std::pin::Pin;
+use std::rc::Rc;
+use std::sync::{Arc, RwLock};
+use std::task::{Context, Poll};
+
+use futures::channel::mpsc;
+use futures::stream::Stream;
+use gloo_events::EventListener;
+use wasm_bindgen::JsCast;
+use web_sys::{EventTarget, PointerEvent};
+
+use crate::settings::Settings;
+use crate::vi::{Command, Motion};
+use crate::Direction;
+
+pub struct Touch {
+    pub receiver: mpsc::UnboundedReceiver<Command>,
+    _listeners: [EventListener; 3],
+}
+
+#[derive(Debug, PartialEq)]
+enum Gesture {
+    Tap,
+    Swipe(Direction),
+}
+
+impl Gesture {
+    /// Classifies a pointer movement, where anything shorter than the threshold is a tap
+    fn from_movement(dx: f64, dy: f64, threshold: f64) -> Gesture {
+        if dx.abs() < threshold && dy.abs() < threshold {
+            Gesture::Tap
+        } else if dx.abs() > dy.abs() {
+            Gesture::Swipe(if dx < 0.0 {
+                Direction::Left
+            } else {
+                Direction::Right
+            })
+        } else {
+            Gesture::Swipe(if dy < 0.0 {
+                Direction::Up
+            } else {
+                Direction::Down
+            })
+        }
+    }
+}
+
+impl Touch {
+    pub fn new(target: &EventTarget, settings: Arc<RwLock<Settings>>) -> Self {
+        let (sender, receiver) = mpsc::unbounded();
+        // Where the current pointer went down
+        let start = Rc::new(Cell::new(None::<(f64, f64)>));
+
+        let on_down = {
+            let start = Rc::clone(&start);
+            move |event: &web_sys::Event| {
+                let event = event.dyn_ref::<PointerEvent>().unwrap();
+                if event.is_primary() {
+                    start.set(Some((
+                        f64::from(event.client_x()),
+                        f64::from(event.client_y()),
+                    )));
+                }
+            }
+        };
+
+        let on_up = {
+            let start = Rc::clone(&start);
+            move |event: &web_sys::Event| {
+                let event = event.dyn_ref::<PointerEvent>().unwrap();
+                let Some((x, y)) = start.take().filter(|_| event.is_primary()) else {
+                    return;
+                };
+                let dx = f64::from(event.client_x()) - x;
+                let dy = f64::from(event.client_y()) - y;
+                let threshold = settings.read().unwrap().swipe_threshold;
+
+                let cmd = match Gesture::from_movement(dx, dy, threshold) {
+                    Gesture::Tap => Command::TogglePause,
+                    Gesture::Swipe(dir) => Command::Move(Motion::Steps(dir, 1)),
+                };
+
+                event.prevent_default();
+                sender.unbounded_send(cmd).unwrap();
+            }
+        };
+
+        let on_cancel = move |_: &web_sys::Event| start.set(None);
+
+        Self {
+            receiver,
+            _listeners: [
+                EventListener::new(target, "pointerdown", on_down),
+                EventListener::new(target, "pointerup", on_up),
+                EventListener::new(target, "pointercancel", on_cancel),
+            ],
+        }
+    }
+}
+
+impl Stream for Touch {
+    type Item = Command;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        Pin::new(&mut self.receiver).poll_next(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn short_movement_should_be_a_tap() {
+        assert_eq!(Gesture::Tap, Gesture::from_movement(3.0, -4.0, 30.0));
+    }
+
+    #[test]
+    fn horizontal_movement_should_swipe_left_or_right() {
+        assert_eq!(
+            Gesture::Swipe(Direction::Left),
+            Gesture::from_movement(-50.0, 10.0, 30.0)
+        );
+        assert_eq!(
+            Gesture::Swipe(Direction::Right),
+            Gesture::from_movement(50.0, -20.0, 30.0)
+        );
+    }
+
+    #[test]
+    fn vertical_movement_should_swipe_up_or_down() {
+        assert_eq!(
+            Gesture::Swipe(Direction::Up),
+            Gesture::from_movement(10.0, -50.0, 30.0)
+        );
+        assert_eq!(
+            Gesture::Swipe(Direction::Down),
+            Gesture::from_movement(0.0, 31.0, 30.0)
+        );
+    }
+
+    #[test]
+    fn threshold_should_decide_between_tap_and_swipe() {
+        assert_eq!(Gesture::Tap, Gesture::from_movement(40.0, 0.0, 50.0));
+        assert_eq!(
+            Gesture::Swipe(Direction::Right),
+            Gesture::from_movement(40.0, 0.0, 20.0)
+        );
+    }
+}
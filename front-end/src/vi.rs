@@ -1,72 +1,691 @@
 //! Converts key presses to commands
+use crate::cmdline::{self, ExCommand, ParseError};
+use crate::debug::DEBUG_KEY;
+use crate::game;
+use crate::keymap::{Action, Keymap};
+use crate::name_entry::InsertKey;
+use crate::settings::Settings;
+use crate::snake::{Position, COLUMNS, ROWS};
 use crate::{Direction, GameStatus};
 use futures::channel::mpsc;
 use futures::stream::Stream;
 use gloo_events::{EventListener, EventListenerOptions};
+use serde::{Deserialize, Serialize};
+use std::cell::{Cell, RefCell};
 use std::pin::Pin;
+use std::rc::Rc;
 use std::sync::{Arc, RwLock};
 use std::task::{Context, Poll};
+use std::{iter, mem};
 use wasm_bindgen::JsCast;
-use web_sys::{EventTarget, KeyboardEvent};
+use web_sys::{Element, EventTarget, HtmlElement, KeyboardEvent};
 
-pub struct Vi {
-    pub receiver: mpsc::UnboundedReceiver<Command>,
-    pub listener: EventListener,
-}
+/// How long to wait for the rest of a key sequence, like vim's `timeoutlen`
+const SEQUENCE_TIMEOUT_MILLIS: f64 = 1000.0;
+/// Longer runs would only go round the board again, or into a wall,
+/// while blocking later input until they're done
+const MAX_COUNT: u32 = if COLUMNS > ROWS { COLUMNS } else { ROWS };
 
 #[derive(Debug)]
 pub enum Command {
     Start,
     Stop,
+    Pause,
+    /// Pause if playing, otherwise start
+    TogglePause,
+    ToggleMute,
+    /// Show or hide the developer overlay
+    ToggleDebug,
     Help,
-    Move(Direction),
+    Move(Motion),
+    /// The command line was opened or edited, and now contains this text
+    EditCmdLine(String),
+    /// The command line was closed without running anything
+    CancelCmdLine,
+    Ex(ExCommand),
+    ExError(ParseError),
+    /// A key typed in insert mode, e.g. while entering a name
+    Insert(InsertKey),
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Motion {
+    /// Move this many steps in a direction, e.g. `3l`
+    Steps(Direction, u32),
+    /// Steer towards a column, e.g. `0` and `$`
+    ToColumn(u32),
+    /// Steer towards a row, e.g. `gg` and `G`
+    ToRow(u32),
+    /// Turn 90 degrees, relative to the current heading
+    Turn(Rotation),
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Rotation {
+    CounterClockwise,
+    Clockwise,
+}
+
+/// How the direction keys steer the snake
+#[derive(Copy, Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum ControlScheme {
+    /// Each direction key moves the snake in that direction
+    #[default]
+    Absolute,
+    /// Two keys turn the snake left and right relative to where it's heading,
+    /// like on old Nokia phones
+    Relative,
+}
+
+impl ControlScheme {
+    /// Converts a motion made with the direction keys to what it means in this scheme
+    fn apply(self, motion: Motion) -> Option<Motion> {
+        match (self, motion) {
+            (ControlScheme::Absolute, _) => Some(motion),
+            (ControlScheme::Relative, Motion::Steps(Direction::Left, _)) => {
+                Some(Motion::Turn(Rotation::CounterClockwise))
+            }
+            (ControlScheme::Relative, Motion::Steps(Direction::Right, _)) => {
+                Some(Motion::Turn(Rotation::Clockwise))
+            }
+            (ControlScheme::Relative, Motion::Steps(_, _)) => None,
+            (ControlScheme::Relative, _) => Some(motion),
+        }
+    }
+}
+
+impl Motion {
+    /// Expands the motion into the steps to take from the given position and heading
+    pub fn directions(self, from: &Position, heading: Direction) -> Vec<Direction> {
+        let towards = |from: u32, to: u32, back, forward| {
+            if to < from {
+                iter::repeat_n(back, (from - to) as usize)
+            } else {
+                iter::repeat_n(forward, (to - from) as usize)
+            }
+        };
+
+        let steps: Vec<Direction> = match self {
+            Motion::Steps(dir, count) => {
+                return iter::repeat_n(dir, count.min(MAX_COUNT) as usize).collect();
+            }
+            Motion::Turn(Rotation::CounterClockwise) => return vec![heading.turn_left()],
+            Motion::Turn(Rotation::Clockwise) => return vec![heading.turn_right()],
+            Motion::ToColumn(col) => towards(
+                from.column(),
+                col.min(COLUMNS - 1),
+                Direction::Left,
+                Direction::Right,
+            )
+            .collect(),
+            Motion::ToRow(row) => towards(
+                from.row(),
+                row.min(ROWS - 1),
+                Direction::Up,
+                Direction::Down,
+            )
+            .collect(),
+        };
+
+        // The snake can't reverse, so go round by turning aside first
+        if steps.first() == Some(&heading.turn_180_degrees()) {
+            let aside = match heading {
+                Direction::Left | Direction::Right if from.row() + 1 < ROWS => Direction::Down,
+                Direction::Left | Direction::Right => Direction::Up,
+                Direction::Up | Direction::Down if from.column() + 1 < COLUMNS => Direction::Right,
+                Direction::Up | Direction::Down => Direction::Left,
+            };
+            return iter::once(aside).chain(steps).collect();
+        }
+        steps
+    }
+}
+
+/// Collects counts and multi-key motions, like `3l` and `gg`, until they are complete
+#[derive(Debug, Default)]
+struct KeySequence {
+    count: Option<u32>,
+    g: bool,
+    /// Timestamp, in milliseconds, of the previous key
+    last_key_at: f64,
+}
+
+#[derive(Debug, PartialEq)]
+enum Feed {
+    /// The key was part of a sequence which is not complete yet
+    Pending,
+    Motion(Motion),
+    /// The key was not a motion, and should be handled elsewhere
+    Other,
+}
+
+impl KeySequence {
+    fn feed(&mut self, key: &str, action: Option<Action>, now: f64) -> Feed {
+        if now - self.last_key_at > SEQUENCE_TIMEOUT_MILLIS {
+            self.reset();
+        }
+        self.last_key_at = now;
+
+        let count = self.count.take();
+        let g = mem::take(&mut self.g);
+        let steps = |dir| Feed::Motion(Motion::Steps(dir, count.unwrap_or(1)));
+        let digit = key.parse::<u32>().ok().filter(|_| key.len() == 1);
+
+        match (key, digit, action) {
+            ("g", _, _) if g => Feed::Motion(Motion::ToRow(count.map_or(0, |c| c - 1))),
+            _ if g => Feed::Other,
+            ("g", _, _) => {
+                self.count = count;
+                self.g = true;
+                Feed::Pending
+            }
+            ("0", _, _) if count.is_none() => Feed::Motion(Motion::ToColumn(0)),
+            (_, Some(digit), _) => {
+                let count = count.unwrap_or(0).saturating_mul(10).saturating_add(digit);
+                self.count = Some(count.min(MAX_COUNT));
+                Feed::Pending
+            }
+            (_, _, Some(Action::Left)) => steps(Direction::Left),
+            (_, _, Some(Action::Down)) => steps(Direction::Down),
+            (_, _, Some(Action::Up)) => steps(Direction::Up),
+            (_, _, Some(Action::Right)) => steps(Direction::Right),
+            ("$", _, _) => Feed::Motion(Motion::ToColumn(COLUMNS - 1)),
+            ("G", _, _) => Feed::Motion(Motion::ToRow(count.map_or(ROWS - 1, |c| c - 1))),
+            _ => Feed::Other,
+        }
+    }
+
+    fn reset(&mut self) {
+        self.count = None;
+        self.g = false;
+    }
+}
+
+/// Vi's modes, turning key presses into commands without knowing about the DOM
+#[derive(Debug, Default)]
+pub struct Vi {
+    /// The command line being edited, if it's open
+    cmdline: Option<String>,
+    keys: KeySequence,
 }
 
 impl Vi {
-    pub fn new(target: &EventTarget, status: Arc<RwLock<GameStatus>>) -> Self {
+    /// Handles a key, pressed at the given timestamp in milliseconds.
+    /// Returns the command it completed, if any.
+    pub fn key(
+        &mut self,
+        key: &str,
+        now: f64,
+        keymap: &Keymap,
+        scheme: ControlScheme,
+    ) -> Option<Command> {
+        let Some(line) = self.cmdline.as_mut() else {
+            return self.normal_mode_key(key, now, keymap, scheme);
+        };
+
+        match key {
+            "Escape" => {
+                self.cmdline = None;
+                Some(Command::CancelCmdLine)
+            }
+            "Enter" => self
+                .cmdline
+                .take()
+                .map(|line| cmdline::parse(&line))
+                .map(|cmd| cmd.map_or_else(Command::ExError, Command::Ex)),
+            "Backspace" if line.is_empty() => {
+                self.cmdline = None;
+                Some(Command::CancelCmdLine)
+            }
+            "Backspace" => {
+                line.pop();
+                Some(Command::EditCmdLine(line.clone()))
+            }
+            "Tab" => {
+                *line = cmdline::complete(line);
+                Some(Command::EditCmdLine(line.clone()))
+            }
+            _ if key.chars().count() == 1 => {
+                line.push_str(key);
+                Some(Command::EditCmdLine(line.clone()))
+            }
+            _ => None,
+        }
+    }
+
+    fn normal_mode_key(
+        &mut self,
+        key: &str,
+        now: f64,
+        keymap: &Keymap,
+        scheme: ControlScheme,
+    ) -> Option<Command> {
+        if key == DEBUG_KEY {
+            return Some(Command::ToggleDebug);
+        }
+        let action = keymap.action(key);
+        match (self.keys.feed(key, action, now), action) {
+            (Feed::Pending, _) => None,
+            (Feed::Motion(motion), _) => scheme.apply(motion).map(Command::Move),
+            (_, Some(Action::Start)) => Some(Command::Start),
+            (_, Some(Action::Stop)) => Some(Command::Stop),
+            (_, Some(Action::Help)) => Some(Command::Help),
+            (_, Some(Action::Mute)) => Some(Command::ToggleMute),
+            (_, Some(Action::CommandLine)) => {
+                self.cmdline = Some(String::new());
+                Some(Command::EditCmdLine(String::new()))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// What a key does in insert mode, where it's text rather than a command
+fn insert_key(key: &str) -> Option<InsertKey> {
+    match key {
+        "Enter" => Some(InsertKey::Enter),
+        "Escape" => Some(InsertKey::Escape),
+        "Backspace" => Some(InsertKey::Backspace),
+        _ => {
+            let mut chars = key.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => Some(InsertKey::Char(c)),
+                _ => None,
+            }
+        }
+    }
+}
+
+/// Feeds key presses on a DOM element to [`Vi`], or straight through as text in insert mode.
+/// Clicking a button with a `data-action` attribute presses the first key bound to the action.
+/// Keys typed into form controls are left to them.
+pub struct Keyboard {
+    pub receiver: mpsc::UnboundedReceiver<Command>,
+    _listeners: [EventListener; 2],
+}
+
+impl Keyboard {
+    pub fn new(
+        target: &EventTarget,
+        keymap: Arc<RwLock<Keymap>>,
+        settings: Arc<RwLock<Settings>>,
+        insert_mode: Rc<Cell<bool>>,
+        status: Rc<Cell<GameStatus>>,
+    ) -> Self {
         let (sender, receiver) = mpsc::unbounded();
-        let listener = EventListener::new_with_options(
-            target,
-            "keydown",
-            EventListenerOptions::enable_prevent_default(),
-            move |event| {
-                let event = event.dyn_ref::<KeyboardEvent>().unwrap();
-                let key: &str = &event.key();
-                let status = *status.read().unwrap();
-                let dir = match key {
-                    "h" | "ArrowLeft" if status == GameStatus::Playing => {
-                        Some(Command::Move(Direction::Left))
-                    }
-                    "j" | "ArrowDown" if status == GameStatus::Playing => {
-                        Some(Command::Move(Direction::Down))
-                    }
-                    "k" | "ArrowUp" if status == GameStatus::Playing => {
-                        Some(Command::Move(Direction::Up))
-                    }
-                    "l" | "ArrowRight" if status == GameStatus::Playing => {
-                        Some(Command::Move(Direction::Right))
-                    }
-                    " " if status != GameStatus::Playing => Some(Command::Start),
-                    "q" if status == GameStatus::Playing => Some(Command::Stop),
-                    "?" => Some(Command::Help),
-                    _ => None,
+        let mut vi = Vi::default();
+        // Sends the command for a key, returning whether the game uses it
+        let press = {
+            let keymap = Arc::clone(&keymap);
+            let insert_mode = Rc::clone(&insert_mode);
+            Rc::new(RefCell::new(move |key: &str| {
+                let scheme = settings.read().unwrap().control_scheme;
+                let cmd = if insert_mode.get() {
+                    insert_key(key).map(Command::Insert)
+                } else {
+                    vi.key(key, js_sys::Date::now(), &keymap.read().unwrap(), scheme)
                 };
+                cmd.is_some_and(|cmd| {
+                    let handled = game::handles(status.get(), &cmd);
+                    sender.unbounded_send(cmd).unwrap();
+                    handled
+                })
+            }))
+        };
 
-                if let Some(dir) = dir {
+        let on_keydown = {
+            let press = Rc::clone(&press);
+            move |event: &web_sys::Event| {
+                let event = event.dyn_ref::<KeyboardEvent>().unwrap();
+                let on_form_control = event
+                    .target()
+                    .and_then(|target| target.dyn_into::<Element>().ok())
+                    .is_some_and(|element| {
+                        matches!(
+                            element.tag_name().as_str(),
+                            "INPUT" | "SELECT" | "TEXTAREA" | "BUTTON"
+                        )
+                    });
+                // Leaves shortcuts like ctrl+r to the browser, but still types what AltGr makes
+                let shortcut = (event.ctrl_key() || event.meta_key() || event.alt_key())
+                    && !event.get_modifier_state("AltGraph");
+                if on_form_control || shortcut {
+                    return;
+                }
+                if (press.borrow_mut())(&event.key()) {
                     event.prevent_default();
-                    sender.unbounded_send(dir).unwrap();
-                };
-            },
-        );
+                }
+            }
+        };
+
+        let on_click = move |event: &web_sys::Event| {
+            let Some(button) = event
+                .target()
+                .and_then(|target| target.dyn_into::<Element>().ok())
+                .and_then(|element| element.closest("[data-action]").ok().flatten())
+            else {
+                return;
+            };
+            // Otherwise the focused button would keep keys from reaching the game
+            if let Some(button) = button.dyn_ref::<HtmlElement>() {
+                button
+                    .blur()
+                    .unwrap_or_else(|e| log::error!("Failed to blur due to {e:?}"));
+            }
+            // The keys would be typed into the name instead
+            if insert_mode.get() {
+                return;
+            }
+            let Some(action) = button
+                .get_attribute("data-action")
+                .and_then(|name| Action::from_name(&name))
+            else {
+                return;
+            };
+            let key = keymap.read().unwrap().keys(action).first().cloned();
+            if let Some(key) = key {
+                (press.borrow_mut())(&key);
+            }
+        };
 
-        Self { receiver, listener }
+        Self {
+            receiver,
+            _listeners: [
+                EventListener::new_with_options(
+                    target,
+                    "keydown",
+                    EventListenerOptions::enable_prevent_default(),
+                    on_keydown,
+                ),
+                EventListener::new(target, "click", on_click),
+            ],
+        }
     }
 }
 
-impl Stream for Vi {
+impl Stream for Keyboard {
     type Item = Command;
 
     fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
         Pin::new(&mut self.receiver).poll_next(cx)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn feed_all(keys: &str) -> Vec<Feed> {
+        let keymap = Keymap::default();
+        let mut seq = KeySequence::default();
+        keys.chars()
+            .map(|key| key.to_string())
+            .map(|key| seq.feed(&key, keymap.action(&key), 1.0))
+            .collect()
+    }
+
+    #[test]
+    fn direction_key_should_be_a_single_step() {
+        assert_eq!(
+            vec![Feed::Motion(Motion::Steps(Direction::Right, 1))],
+            feed_all("l")
+        );
+    }
+
+    #[test]
+    fn rebound_direction_key_should_move() {
+        let mut seq = KeySequence::default();
+        assert_eq!(
+            Feed::Motion(Motion::Steps(Direction::Left, 1)),
+            seq.feed("a", Some(Action::Left), 1.0)
+        );
+    }
+
+    #[test]
+    fn count_should_repeat_the_direction() {
+        assert_eq!(
+            Some(&Feed::Motion(Motion::Steps(Direction::Down, 12))),
+            feed_all("12j").last()
+        );
+    }
+
+    #[test]
+    fn zero_should_be_part_of_count_after_another_digit() {
+        assert_eq!(
+            vec![
+                Feed::Pending,
+                Feed::Pending,
+                Feed::Motion(Motion::Steps(Direction::Left, 10))
+            ],
+            feed_all("10h")
+        );
+    }
+
+    #[test]
+    fn zero_and_dollar_should_steer_to_row_edges() {
+        assert_eq!(vec![Feed::Motion(Motion::ToColumn(0))], feed_all("0"));
+        assert_eq!(
+            vec![Feed::Motion(Motion::ToColumn(COLUMNS - 1))],
+            feed_all("$")
+        );
+    }
+
+    #[test]
+    fn gg_and_big_g_should_steer_to_top_and_bottom() {
+        assert_eq!(
+            vec![Feed::Pending, Feed::Motion(Motion::ToRow(0))],
+            feed_all("gg")
+        );
+        assert_eq!(vec![Feed::Motion(Motion::ToRow(ROWS - 1))], feed_all("G"));
+    }
+
+    #[test]
+    fn count_before_big_g_should_steer_to_that_row() {
+        assert_eq!(Some(&Feed::Motion(Motion::ToRow(4))), feed_all("5G").last());
+    }
+
+    #[test]
+    fn unfinished_sequence_should_time_out() {
+        let mut seq = KeySequence::default();
+        assert_eq!(Feed::Pending, seq.feed("3", None, 1.0));
+        assert_eq!(
+            Feed::Motion(Motion::Steps(Direction::Right, 1)),
+            seq.feed(
+                "l",
+                Some(Action::Right),
+                1.0 + SEQUENCE_TIMEOUT_MILLIS + 1.0
+            )
+        );
+    }
+
+    #[test]
+    fn other_keys_should_cancel_pending_sequence() {
+        assert_eq!(
+            vec![
+                Feed::Pending,
+                Feed::Other,
+                Feed::Motion(Motion::ToColumn(0))
+            ],
+            feed_all("gq0")
+        );
+    }
+
+    #[test]
+    fn relative_scheme_should_turn_with_left_and_right() {
+        let scheme = ControlScheme::Relative;
+        assert_eq!(
+            Some(Motion::Turn(Rotation::CounterClockwise)),
+            scheme.apply(Motion::Steps(Direction::Left, 1))
+        );
+        assert_eq!(
+            Some(Motion::Turn(Rotation::Clockwise)),
+            scheme.apply(Motion::Steps(Direction::Right, 3))
+        );
+        assert_eq!(None, scheme.apply(Motion::Steps(Direction::Up, 1)));
+        assert_eq!(Some(Motion::ToColumn(0)), scheme.apply(Motion::ToColumn(0)));
+    }
+
+    #[test]
+    fn absolute_scheme_should_keep_motions() {
+        let motion = Motion::Steps(Direction::Up, 2);
+        assert_eq!(Some(motion), ControlScheme::Absolute.apply(motion));
+    }
+
+    #[test]
+    fn turn_should_expand_relative_to_heading() {
+        let from = Position { x: 0.0, y: 0.0 };
+        assert_eq!(
+            vec![Direction::Up],
+            Motion::Turn(Rotation::CounterClockwise).directions(&from, Direction::Right)
+        );
+        assert_eq!(
+            vec![Direction::Right],
+            Motion::Turn(Rotation::Clockwise).directions(&from, Direction::Up)
+        );
+    }
+
+    #[test]
+    fn steps_should_expand_to_repeated_direction() {
+        let from = Position { x: 0.0, y: 0.0 };
+        assert_eq!(
+            vec![Direction::Down; 3],
+            Motion::Steps(Direction::Down, 3).directions(&from, Direction::Right)
+        );
+    }
+
+    #[test]
+    fn to_column_should_expand_to_steps_towards_it() {
+        let from = Position { x: 50.0, y: 0.0 };
+        assert_eq!(
+            vec![Direction::Left; 2],
+            Motion::ToColumn(0).directions(&from, Direction::Down)
+        );
+    }
+
+    #[test]
+    fn motions_behind_the_snake_should_turn_aside_first() {
+        let from = Position { x: 50.0, y: 0.0 };
+        assert_eq!(
+            vec![Direction::Down, Direction::Left, Direction::Left],
+            Motion::ToColumn(0).directions(&from, Direction::Right)
+        );
+
+        let bottom_right = Position {
+            x: f64::from(COLUMNS - 1) * 25.0,
+            y: f64::from(ROWS - 1) * 25.0,
+        };
+        assert_eq!(
+            Some(&Direction::Left),
+            Motion::ToRow(0)
+                .directions(&bottom_right, Direction::Down)
+                .first()
+        );
+    }
+
+    #[test]
+    fn long_counts_should_be_capped() {
+        let from = Position { x: 0.0, y: 0.0 };
+        assert_eq!(
+            MAX_COUNT as usize,
+            Motion::Steps(Direction::Right, 99_999)
+                .directions(&from, Direction::Right)
+                .len()
+        );
+    }
+
+    #[test]
+    fn to_row_should_expand_to_steps_towards_it() {
+        let from = Position { x: 0.0, y: 0.0 };
+        assert_eq!(
+            vec![Direction::Down; ROWS as usize - 1],
+            Motion::ToRow(ROWS - 1).directions(&from, Direction::Right)
+        );
+        assert!(Motion::ToRow(0)
+            .directions(&from, Direction::Right)
+            .is_empty());
+    }
+
+    fn press_all(vi: &mut Vi, keys: &[&str]) -> Vec<Option<Command>> {
+        let keymap = Keymap::default();
+        keys.iter()
+            .map(|key| vi.key(key, 1.0, &keymap, ControlScheme::Absolute))
+            .collect()
+    }
+
+    #[test]
+    fn start_stop_and_help_keys_should_give_commands() {
+        let mut vi = Vi::default();
+        assert!(matches!(
+            press_all(&mut vi, &[" ", "q", "?"]).as_slice(),
+            [
+                Some(Command::Start),
+                Some(Command::Stop),
+                Some(Command::Help)
+            ]
+        ));
+    }
+
+    #[test]
+    fn debug_key_should_toggle_debug_without_a_binding() {
+        let mut vi = Vi::default();
+        assert!(matches!(
+            press_all(&mut vi, &[DEBUG_KEY]).as_slice(),
+            [Some(Command::ToggleDebug)]
+        ));
+    }
+
+    #[test]
+    fn insert_mode_keys_should_be_text() {
+        assert_eq!(Some(InsertKey::Char('q')), insert_key("q"));
+        assert_eq!(Some(InsertKey::Char('ø')), insert_key("ø"));
+        assert_eq!(Some(InsertKey::Escape), insert_key("Escape"));
+        assert_eq!(None, insert_key("Shift"));
+    }
+
+    #[test]
+    fn colon_should_open_cmdline_and_enter_should_run_it() {
+        let mut vi = Vi::default();
+        let commands = press_all(&mut vi, &[":", "q", "Enter"]);
+
+        assert!(matches!(
+            commands.as_slice(),
+            [
+                Some(Command::EditCmdLine(_)),
+                Some(Command::EditCmdLine(line)),
+                Some(Command::Ex(ExCommand::Quit))
+            ] if line == "q"
+        ));
+        // Back in normal mode
+        assert!(matches!(
+            press_all(&mut vi, &["q"]).as_slice(),
+            [Some(Command::Stop)]
+        ));
+    }
+
+    #[test]
+    fn escape_should_cancel_cmdline() {
+        let mut vi = Vi::default();
+        assert!(matches!(
+            press_all(&mut vi, &[":", "x", "Escape"]).last(),
+            Some(Some(Command::CancelCmdLine))
+        ));
+    }
+
+    #[test]
+    fn backspace_on_empty_cmdline_should_cancel_it() {
+        let mut vi = Vi::default();
+        assert!(matches!(
+            press_all(&mut vi, &[":", "Backspace"]).last(),
+            Some(Some(Command::CancelCmdLine))
+        ));
+    }
+
+    #[test]
+    fn unknown_ex_command_should_give_error() {
+        let mut vi = Vi::default();
+        assert!(matches!(
+            press_all(&mut vi, &[":", "x", "Enter"]).last(),
+            Some(Some(Command::ExError(ParseError::NotACommand(_))))
+        ));
+    }
+}
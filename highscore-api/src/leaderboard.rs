@@ -0,0 +1,78 @@
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    Json,
+};
+use bson::doc;
+use futures::stream::TryStreamExt;
+use highscore_types::{HighScoreDocument, HighScoreDto, Period, LEADERBOARD_DEPTH};
+use mongodb::{bson::DateTime, Database};
+use serde::Deserialize;
+use time::OffsetDateTime;
+
+use crate::top_ten::to_dto;
+
+const GENERIC_DB_ERROR: &str = "An error occured trying to fetch highscores from the database";
+
+const DEFAULT_LIMIT: u32 = 10;
+const MAX_LIMIT: u32 = 100;
+
+#[derive(Deserialize)]
+pub struct Params {
+    #[serde(default)]
+    pub period: Period,
+    #[serde(default = "default_limit")]
+    pub limit: u32,
+    #[serde(default)]
+    pub offset: u32,
+}
+
+fn default_limit() -> u32 {
+    DEFAULT_LIMIT
+}
+
+/// A page of the highscores in a period, best first. Ties are ordered by who got there first.
+pub async fn handle_leaderboard(
+    Query(params): Query<Params>,
+    State(db): State<Database>,
+) -> Result<Json<Vec<HighScoreDto>>, (StatusCode, &'static str)> {
+    if params.limit == 0 || params.limit > MAX_LIMIT {
+        return Err((StatusCode::BAD_REQUEST, "limit must be between 1 and 100"));
+    }
+    // Older highscores further down are removed by the cleanup job
+    if params.offset >= LEADERBOARD_DEPTH {
+        return Err((StatusCode::BAD_REQUEST, "offset must be below 100"));
+    }
+    let limit = params.limit.min(LEADERBOARD_DEPTH - params.offset);
+
+    let filter = params
+        .period
+        .start(OffsetDateTime::now_utc())
+        .map(DateTime::from_time_0_3)
+        .map(|since| doc! { "timestamp": {"$gte": since}})
+        .unwrap_or_else(|| doc! {});
+
+    let collection = db.collection::<HighScoreDocument>("highscore");
+
+    let scores = collection
+        .find(filter)
+        .sort(doc! { "score": -1, "timestamp": 1 })
+        .skip(u64::from(params.offset))
+        .limit(i64::from(limit))
+        .await
+        .map_err(|e| {
+            tracing::error!(?e, "Failed querying leaderboard to db cursor");
+            (StatusCode::INTERNAL_SERVER_ERROR, GENERIC_DB_ERROR)
+        })?
+        .try_collect::<Vec<HighScoreDocument>>()
+        .await
+        .map_err(|e| {
+            tracing::error!(?e, "Failed collecting leaderboard from db cursor");
+            (StatusCode::INTERNAL_SERVER_ERROR, GENERIC_DB_ERROR)
+        })?
+        .iter()
+        .map(to_dto)
+        .collect();
+
+    Ok(Json(scores))
+}
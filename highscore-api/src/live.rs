@@ -0,0 +1,32 @@
+use std::convert::Infallible;
+
+use axum::{
+    extract::State,
+    response::sse::{Event, KeepAlive, Sse},
+};
+use futures::stream::{self, Stream};
+use highscore_types::HighScoreDto;
+use tokio::sync::broadcast::{self, error::RecvError};
+
+/// Streams highscores as they're submitted, as server-sent events named `highscore`.
+/// Only highscores submitted to this instance of the api are included.
+pub async fn handle_live(
+    State(new_highscores): State<broadcast::Sender<HighScoreDto>>,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let events = stream::unfold(new_highscores.subscribe(), |mut receiver| async move {
+        loop {
+            match receiver.recv().await {
+                Ok(highscore) => match Event::default().event("highscore").json_data(&highscore) {
+                    Ok(event) => return Some((Ok(event), receiver)),
+                    Err(e) => tracing::error!(?e, "Failed to serialize highscore for live event"),
+                },
+                Err(RecvError::Lagged(missed)) => {
+                    tracing::warn!(missed, "Live listener fell behind and missed highscores");
+                }
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    });
+
+    Sse::new(events).keep_alive(KeepAlive::default())
+}
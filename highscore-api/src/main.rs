@@ -1,32 +1,64 @@
 use std::{env, error::Error, net::SocketAddr};
 
 use axum::{
+    extract::FromRef,
     routing::{get, post},
     Router,
 };
 use axum_tracing_opentelemetry::middleware::{OtelAxumLayer, OtelInResponseLayer};
+use highscore_types::HighScoreDto;
 use init_tracing_opentelemetry::tracing_subscriber_ext;
 use mongodb::{options::ClientOptions, Client, Database};
-use tokio::net::TcpListener;
+use tokio::{net::TcpListener, sync::broadcast};
 
 mod health;
+mod leaderboard;
+mod live;
+mod rank;
 mod submit;
 mod top_ten;
 
+/// How many new highscores live listeners can fall behind before missing some
+const LIVE_CAPACITY: usize = 16;
+
+#[derive(Clone)]
+struct AppState {
+    db: Database,
+    /// New highscores submitted to this instance, for pushing to live listeners
+    new_highscores: broadcast::Sender<HighScoreDto>,
+}
+
+impl FromRef<AppState> for Database {
+    fn from_ref(state: &AppState) -> Self {
+        state.db.clone()
+    }
+}
+
+impl FromRef<AppState> for broadcast::Sender<HighScoreDto> {
+    fn from_ref(state: &AppState) -> Self {
+        state.new_highscores.clone()
+    }
+}
+
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error>> {
     let _guard = tracing_subscriber_ext::init_subscribers()?;
 
     let db = get_db_handle().await?;
+    submit::create_idempotency_index(&db).await?;
+    let (new_highscores, _) = broadcast::channel(LIVE_CAPACITY);
 
     let app = Router::new()
         .route("/topten", get(top_ten::handle_top_ten))
+        .route("/leaderboard", get(leaderboard::handle_leaderboard))
+        .route("/rank", get(rank::handle_rank))
         .route("/submit", post(submit::submit))
+        .route("/live", get(live::handle_live))
         .route("/readyz", get(health::ready))
         .route("/livez", get(health::live))
         .layer(OtelInResponseLayer)
         .layer(OtelAxumLayer::default())
-        .with_state(db);
+        .with_state(AppState { db, new_highscores });
 
     let addr = env::var("LISTEN_ADDR")
         .unwrap_or(String::from("[::]:3000"))
@@ -0,0 +1,46 @@
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    Json,
+};
+use bson::doc;
+use highscore_types::{HighScoreDocument, Period, RankDto};
+use mongodb::{bson::DateTime, Database};
+use serde::Deserialize;
+use time::OffsetDateTime;
+
+const GENERIC_DB_ERROR: &str = "An error occured trying to rank the score";
+
+#[derive(Deserialize)]
+pub struct Params {
+    pub score: u8,
+}
+
+/// The rank the score would get in each period's leaderboard, if it was submitted now
+pub async fn handle_rank(
+    Query(params): Query<Params>,
+    State(db): State<Database>,
+) -> Result<Json<Vec<RankDto>>, (StatusCode, &'static str)> {
+    let collection = db.collection::<HighScoreDocument>("highscore");
+    let now = OffsetDateTime::now_utc();
+
+    let mut ranks = Vec::with_capacity(Period::ALL.len());
+    for period in Period::ALL {
+        // Ties go below the scores which were there first
+        let mut filter = doc! { "score": { "$gte": i32::from(params.score) } };
+        if let Some(start) = period.start(now) {
+            filter.insert("timestamp", doc! { "$gte": DateTime::from_time_0_3(start) });
+        }
+
+        let better_or_equal = collection.count_documents(filter).await.map_err(|e| {
+            tracing::error!(?e, ?period, "Failed counting better highscores");
+            (StatusCode::INTERNAL_SERVER_ERROR, GENERIC_DB_ERROR)
+        })?;
+        ranks.push(RankDto {
+            period,
+            rank: better_or_equal + 1,
+        });
+    }
+
+    Ok(Json(ranks))
+}
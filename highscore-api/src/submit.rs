@@ -1,27 +1,162 @@
-use axum::{extract::State, http::StatusCode, Json};
-use highscore_types::{HighScoreDocument, HighScoreDto};
-use mongodb::Database;
+use axum::{
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    Json,
+};
+use bson::{doc, DateTime};
+use highscore_types::{HighScoreDocument, HighScoreDto, Period};
+use mongodb::{
+    error::{ErrorKind, WriteError, WriteFailure},
+    options::IndexOptions,
+    Collection, Database, IndexModel,
+};
+use time::OffsetDateTime;
+use tokio::sync::broadcast;
+
+use crate::top_ten::to_dto;
 
 const GENERIC_DB_ERROR: &str = "An error occured trying to persist highscore to database";
 
+/// Header clients can set to the same value when retrying a submission
+const IDEMPOTENCY_KEY: &str = "idempotency-key";
+
+/// Mongodb's error code for inserting a document which breaks a unique index
+const DUPLICATE_KEY: i32 = 11000;
+
+/// Only scores which make this year's top ten are kept, like the front-end asks for
+const QUALIFYING_RANK: u64 = 10;
+
+/// Makes the database refuse a second highscore with the same idempotency key,
+/// even if both are submitted at the same time
+pub async fn create_idempotency_index(db: &Database) -> Result<(), mongodb::error::Error> {
+    let index = IndexModel::builder()
+        .keys(doc! { "idempotency_key": 1 })
+        .options(
+            IndexOptions::builder()
+                .unique(true)
+                .partial_filter_expression(doc! { "idempotency_key": { "$exists": true } })
+                .build(),
+        )
+        .build();
+    db.collection::<HighScoreDocument>("highscore")
+        .create_index(index)
+        .await?;
+    Ok(())
+}
+
+fn is_duplicate_key(e: &mongodb::error::Error) -> bool {
+    matches!(
+        *e.kind,
+        ErrorKind::Write(WriteFailure::WriteError(WriteError {
+            code: DUPLICATE_KEY,
+            ..
+        }))
+    )
+}
+
+/// Whether the score would make this year's top ten. A retry isn't counted against itself.
+async fn qualifies(
+    collection: &Collection<HighScoreDocument>,
+    doc: &HighScoreDocument,
+) -> Result<bool, mongodb::error::Error> {
+    // Ties go below the scores which were there first, like in `rank`
+    let mut filter = doc! { "score": { "$gte": i32::from(doc.score) } };
+    if let Some(start) = Period::Year.start(OffsetDateTime::now_utc()) {
+        filter.insert("timestamp", doc! { "$gte": DateTime::from_time_0_3(start) });
+    }
+    if let Some(key) = &doc.idempotency_key {
+        filter.insert("idempotency_key", doc! { "$ne": key });
+    }
+    let better_or_equal = collection.count_documents(filter).await?;
+    Ok(better_or_equal < QUALIFYING_RANK)
+}
+
 pub async fn submit(
     State(db): State<Database>,
+    State(new_highscores): State<broadcast::Sender<HighScoreDto>>,
+    headers: HeaderMap,
     Json(payload): Json<HighScoreDto>,
 ) -> Result<(StatusCode, Json<HighScoreDto>), (StatusCode, String)> {
     let collection = db.collection::<HighScoreDocument>("highscore");
 
-    let doc = HighScoreDocument::try_from_dto(&payload).map_err(|e| {
+    let mut doc = HighScoreDocument::try_from_dto(&payload).map_err(|e| {
         tracing::warn!(?e, "Mapping from highscore dto to doc failed validation");
         (StatusCode::UNPROCESSABLE_ENTITY, e)
     })?;
 
-    collection.insert_one(doc).await.map_err(|e| {
-        tracing::error!(?e, "Failed to persist highscore to database");
+    if let Some(key) = headers.get(IDEMPOTENCY_KEY) {
+        let key = key.to_str().map_err(|e| {
+            tracing::warn!(?e, "Idempotency key was not valid text");
+            (
+                StatusCode::BAD_REQUEST,
+                String::from("Invalid idempotency key"),
+            )
+        })?;
+        doc = doc.with_idempotency_key(key).map_err(|e| {
+            tracing::warn!(?e, "Idempotency key failed validation");
+            (StatusCode::BAD_REQUEST, e)
+        })?;
+    }
+
+    let qualifies = qualifies(&collection, &doc).await.map_err(|e| {
+        tracing::error!(?e, "Failed to check if the score is a highscore");
         (
             StatusCode::INTERNAL_SERVER_ERROR,
             GENERIC_DB_ERROR.to_string(),
         )
     })?;
+    if !qualifies {
+        tracing::info!(
+            score = doc.score,
+            "Score didn't make the top ten, not storing it"
+        );
+        return Err((
+            StatusCode::UNPROCESSABLE_ENTITY,
+            String::from("Score is not a highscore"),
+        ));
+    }
+
+    let highscore = to_dto(&doc);
+    match collection.insert_one(doc).await {
+        Ok(_) => {}
+        // The unique index on the idempotency key caught a retry
+        Err(e) if is_duplicate_key(&e) => {
+            tracing::info!("Highscore was already submitted, not storing it again");
+            return Ok((StatusCode::OK, Json(highscore)));
+        }
+        Err(e) => {
+            tracing::error!(?e, "Failed to persist highscore to database");
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                GENERIC_DB_ERROR.to_string(),
+            ));
+        }
+    }
+
+    // Fails only if nobody is listening
+    let _ = new_highscores.send(highscore.clone());
+
+    Ok((StatusCode::CREATED, Json(highscore)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn response_should_not_include_the_player_id() {
+        let payload = HighScoreDto {
+            user_name: String::from("Player"),
+            score: 50,
+            timestamp: None,
+            player_id: Some(String::from("67e55044-10b1-426f-9247-bb680e5fe0c8")),
+        };
+        let doc = HighScoreDocument::try_from_dto(&payload).unwrap();
+        assert_eq!(doc.player_id, payload.player_id);
 
-    Ok((StatusCode::CREATED, Json(payload)))
+        let response = to_dto(&doc);
+        assert_eq!(response.player_id, None);
+        assert_eq!(response.user_name, payload.user_name);
+        assert_eq!(response.score, payload.score);
+    }
 }
@@ -32,7 +32,7 @@ pub async fn handle_top_ten(
 
     let scores = collection
         .find(filter)
-        .sort(doc! { "score": -1 })
+        .sort(doc! { "score": -1, "timestamp": 1 })
         .limit(10)
         .await
         .map_err(|e| {
@@ -46,11 +46,18 @@ pub async fn handle_top_ten(
             (StatusCode::INTERNAL_SERVER_ERROR, GENERIC_DB_ERROR)
         })?
         .iter()
-        .map(|doc| HighScoreDto {
-            user_name: doc.user_name.to_string(),
-            score: doc.score,
-        })
+        .map(to_dto)
         .collect();
 
     Ok(Json(scores))
 }
+
+/// The public part of a stored highscore
+pub fn to_dto(doc: &HighScoreDocument) -> HighScoreDto {
+    HighScoreDto {
+        user_name: doc.user_name.to_string(),
+        score: doc.score,
+        timestamp: Some(doc.timestamp.to_time_0_3()),
+        player_id: None,
+    }
+}
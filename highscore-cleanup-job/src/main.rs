@@ -2,62 +2,48 @@ use std::{collections::HashSet, env, error::Error};
 
 use bson::{doc, DateTime};
 use futures::TryStreamExt;
-use highscore_types::HighScoreDocument;
+use highscore_types::{HighScoreDocument, Period, LEADERBOARD_DEPTH};
 use init_tracing_opentelemetry::tracing_subscriber_ext;
 use mongodb::{options::ClientOptions, Client, Database};
 use time::OffsetDateTime;
 
-const TO_KEEP_COUNT: u8 = 15;
-
+/// Keeps every highscore which can be seen on a leaderboard page, in any period
 async fn do_cleanup(db: &Database) -> Result<(), Box<dyn Error>> {
     let collection = db.collection::<HighScoreDocument>("highscore");
+    let now = OffsetDateTime::now_utc();
+
+    let mut to_keep = HashSet::new();
+    for period in Period::ALL {
+        let filter = period
+            .start(now)
+            .map(DateTime::from_time_0_3)
+            .map(|since| doc! { "timestamp": {"$gte": since}})
+            .unwrap_or_else(|| doc! {});
+
+        let on_leaderboard = collection
+            .find(filter)
+            .sort(doc! { "score": -1, "timestamp": 1 })
+            .limit(i64::from(LEADERBOARD_DEPTH))
+            .await?
+            .try_collect::<Vec<_>>()
+            .await?;
 
-    let ok_to_delete_all_time = collection
-        .find(doc! {})
-        .sort(doc! { "score": -1})
-        .skip(u64::from(TO_KEEP_COUNT))
-        .await?
-        .try_collect::<Vec<_>>()
-        .await?;
-
-    let ok_to_delete_all_time = ok_to_delete_all_time
-        .iter()
-        .filter_map(|hs| hs.id)
-        .collect::<HashSet<_>>();
-
-    let start_of_this_year = DateTime::builder()
-        .year(OffsetDateTime::now_utc().year())
-        .month(1)
-        .day(1)
-        .build()?;
-
-    let ok_to_delete_this_year = collection
-        .find(doc! { "timestamp": {"$gte": start_of_this_year}})
-        .sort(doc! { "score": -1})
-        .skip(u64::from(TO_KEEP_COUNT))
-        .await?
-        .try_collect::<Vec<_>>()
-        .await?;
+        to_keep.extend(on_leaderboard.iter().filter_map(|hs| hs.id));
+    }
 
-    let ok_to_delete_this_year = ok_to_delete_this_year
-        .iter()
-        .filter_map(|hs| hs.id)
-        .collect::<HashSet<_>>();
+    let to_keep = to_keep.into_iter().collect::<Vec<_>>();
 
-    let to_delete = ok_to_delete_this_year
-        .intersection(&ok_to_delete_all_time)
-        .copied()
-        .collect::<Vec<_>>();
+    // Highscores submitted while the job ran weren't looked at, so they're left alone
+    let res = collection
+        .delete_many(doc! {
+            "_id": {"$nin": to_keep},
+            "timestamp": {"$lt": DateTime::from_time_0_3(now)},
+        })
+        .await?;
 
-    if to_delete.is_empty() {
+    if res.deleted_count == 0 {
         tracing::info!("Nothing to delete");
     } else {
-        tracing::info!(?to_delete, "Ready to delete highscores");
-
-        let res = collection
-            .delete_many(doc! { "_id": {"$in": to_delete}})
-            .await?;
-
         tracing::info!(
             deleted_count = res.deleted_count,
             "Successfully deleted no-longer-needed highscores"
@@ -1,12 +1,37 @@
 use bson::oid::ObjectId;
 use bson::DateTime;
 use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+
+mod period;
+pub use period::Period;
+
+/// How far down every period's leaderboard goes. The cleanup job keeps this many in each.
+pub const LEADERBOARD_DEPTH: u32 = 100;
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub struct HighScoreDto {
     #[serde(rename = "userName")]
     pub user_name: String,
     pub score: u8,
+    /// When the score was submitted. Set by the api, so it's ignored in submissions.
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "time::serde::rfc3339::option"
+    )]
+    pub timestamp: Option<OffsetDateTime>,
+    /// Anonymous id of the player, if they chose to send one. Never returned by the api.
+    #[serde(rename = "playerId", default, skip_serializing_if = "Option::is_none")]
+    pub player_id: Option<String>,
+}
+
+/// Where a score would place in a period's leaderboard
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct RankDto {
+    pub period: Period,
+    /// 1 is the best. Ties go below the scores which were there first.
+    pub rank: u64,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
@@ -16,20 +41,54 @@ pub struct HighScoreDocument {
     pub user_name: String,
     pub score: u8,
     pub timestamp: DateTime,
+    /// Set by clients, so retried submissions aren't stored twice
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub idempotency_key: Option<String>,
+    /// Groups scores from the same player, without them needing an account
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub player_id: Option<String>,
+}
+
+/// Whether the id is a hyphenated uuid, e.g. `67e55044-10b1-426f-9247-bb680e5fe0c8`
+fn is_uuid(id: &str) -> bool {
+    id.len() == 36
+        && id.char_indices().all(|(i, c)| match i {
+            8 | 13 | 18 | 23 => c == '-',
+            _ => c.is_ascii_hexdigit(),
+        })
 }
 
 impl HighScoreDocument {
     const MAX_SCORE: u8 = 144;
+    const MAX_IDEMPOTENCY_KEY_LENGTH: usize = 64;
 
     pub fn try_from_dto(dto: &HighScoreDto) -> Result<Self, String> {
         if dto.score > Self::MAX_SCORE {
             Err(format!("Invalid score {}: too high", dto.score))
+        } else if dto.player_id.as_deref().is_some_and(|id| !is_uuid(id)) {
+            Err(String::from("Invalid player id: must be a uuid"))
         } else {
             Ok(HighScoreDocument {
                 id: None,
                 user_name: dto.user_name.clone(),
                 score: dto.score,
                 timestamp: DateTime::now(),
+                idempotency_key: None,
+                player_id: dto.player_id.clone(),
+            })
+        }
+    }
+
+    pub fn with_idempotency_key(self, key: &str) -> Result<Self, String> {
+        if key.is_empty() || key.len() > Self::MAX_IDEMPOTENCY_KEY_LENGTH {
+            Err(format!(
+                "Invalid idempotency key: must be 1 to {} bytes",
+                Self::MAX_IDEMPOTENCY_KEY_LENGTH
+            ))
+        } else {
+            Ok(HighScoreDocument {
+                idempotency_key: Some(key.to_string()),
+                ..self
             })
         }
     }
@@ -44,6 +103,8 @@ mod tests {
         let dto = HighScoreDto {
             user_name: String::from("Test user"),
             score: 50,
+            timestamp: None,
+            player_id: None,
         };
         let doc = HighScoreDocument::try_from_dto(&dto).unwrap();
 
@@ -56,4 +117,39 @@ mod tests {
                 && doc.timestamp > DateTime::from_millis(DateTime::now().timestamp_millis() - 500)
         );
     }
+
+    #[test]
+    fn it_only_accepts_reasonable_idempotency_keys() {
+        let dto = HighScoreDto {
+            user_name: String::from("Test user"),
+            score: 50,
+            timestamp: None,
+            player_id: None,
+        };
+        let doc = HighScoreDocument::try_from_dto(&dto).unwrap();
+
+        let keyed = doc.clone().with_idempotency_key("abc123").unwrap();
+        assert_eq!(Some(String::from("abc123")), keyed.idempotency_key);
+
+        assert!(doc.clone().with_idempotency_key("").is_err());
+        assert!(doc.with_idempotency_key(&"x".repeat(65)).is_err());
+    }
+
+    #[test]
+    fn it_only_accepts_uuids_as_player_ids() {
+        let dto = HighScoreDto {
+            user_name: String::from("Test user"),
+            score: 50,
+            timestamp: None,
+            player_id: Some(String::from("67e55044-10b1-426f-9247-bb680e5fe0c8")),
+        };
+        let doc = HighScoreDocument::try_from_dto(&dto).unwrap();
+        assert_eq!(dto.player_id, doc.player_id);
+
+        let not_a_uuid = HighScoreDto {
+            player_id: Some(String::from("67e55044_10b1_426f_9247_bb680e5fe0c8")),
+            ..dto
+        };
+        assert!(HighScoreDocument::try_from_dto(&not_a_uuid).is_err());
+    }
 }
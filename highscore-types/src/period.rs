@@ -0,0 +1,87 @@
+use serde::{Deserialize, Serialize};
+use time::{Date, Duration, Month, OffsetDateTime, UtcOffset};
+
+/// A time span leaderboards can be limited to. They all end now, and start at midnight UTC.
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Period {
+    Today,
+    /// Since monday
+    Week,
+    Month,
+    Year,
+    #[default]
+    All,
+}
+
+impl Period {
+    pub const ALL: [Period; 5] = [
+        Period::Today,
+        Period::Week,
+        Period::Month,
+        Period::Year,
+        Period::All,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Period::Today => "today",
+            Period::Week => "week",
+            Period::Month => "month",
+            Period::Year => "year",
+            Period::All => "all",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|period| period.name() == name)
+    }
+
+    /// When the period started, or `None` if it never did
+    pub fn start(self, now: OffsetDateTime) -> Option<OffsetDateTime> {
+        let today = now.to_offset(UtcOffset::UTC).date();
+        let first_day = match self {
+            Period::Today => today,
+            Period::Week => {
+                today - Duration::days(i64::from(today.weekday().number_days_from_monday()))
+            }
+            Period::Month => today.replace_day(1).ok()?,
+            Period::Year => Date::from_calendar_date(today.year(), Month::January, 1).ok()?,
+            Period::All => return None,
+        };
+        Some(first_day.midnight().assume_utc())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::de::value::{self, StrDeserializer};
+    use serde::de::IntoDeserializer;
+    use time::format_description::well_known::Rfc3339;
+
+    fn time(rfc3339: &str) -> OffsetDateTime {
+        OffsetDateTime::parse(rfc3339, &Rfc3339).unwrap()
+    }
+
+    #[test]
+    fn periods_should_start_at_midnight_utc() {
+        // A wednesday, but already thursday in UTC
+        let now = time("2025-04-09T23:30:00-02:00");
+
+        assert_eq!(Some(time("2025-04-10T00:00:00Z")), Period::Today.start(now));
+        assert_eq!(Some(time("2025-04-07T00:00:00Z")), Period::Week.start(now));
+        assert_eq!(Some(time("2025-04-01T00:00:00Z")), Period::Month.start(now));
+        assert_eq!(Some(time("2025-01-01T00:00:00Z")), Period::Year.start(now));
+        assert_eq!(None, Period::All.start(now));
+    }
+
+    #[test]
+    fn names_should_match_the_serialized_form() {
+        for period in Period::ALL {
+            let name: StrDeserializer<'_, value::Error> = period.name().into_deserializer();
+            assert_eq!(Ok(period), Period::deserialize(name));
+            assert_eq!(Some(period), Period::from_name(period.name()));
+        }
+    }
+}
@@ -55,6 +55,8 @@ resource "azurerm_container_app" "highscoreApi" {
 
     # cors must be configured in the azure portal for now
     # because it is currently (July, 2023) not supported by azurerm terraform provider
+    # allowed headers must include content-type and idempotency-key,
+    # which the front-end sends when submitting a highscore
   }
 
   identity {